@@ -4,7 +4,7 @@ use anchor_lang::prelude::*;
 use pyth_lazer_solana_contract::protocol::{
     message::LeEcdsaMessage,
     payload::{PayloadData, PayloadPropertyValue},
-    router::channel_ids::FIXED_RATE_200,
+    router::ChannelId,
 };
 
 declare_id!("FpmpVrP57C6ADT8d4dQp9TkM1vmxohZJ5WEQQc9RGLPY");
@@ -13,15 +13,76 @@ declare_id!("FpmpVrP57C6ADT8d4dQp9TkM1vmxohZJ5WEQQc9RGLPY");
 pub mod solana_anchor {
     use super::*;
 
-    pub fn initialize(ctx: Context<Initialize>, price_feed_id: u32) -> Result<()> {
+    /// `accepted_channels` is the set of channels that updates are accepted from,
+    /// see `channel_bit`. `max_deviation_bps_per_second` limits the price move of an update
+    /// relative to the previous price, per second elapsed between them (0 disables the check),
+    /// and `deviation_mode` selects what happens to updates exceeding it.
+    pub fn initialize(
+        ctx: Context<Initialize>,
+        price_feed_id: u32,
+        accepted_channels: u32,
+        max_deviation_bps_per_second: u32,
+        deviation_mode: DeviationMode,
+    ) -> Result<()> {
         let state = &mut ctx.accounts.state;
         state.price_feed_id = price_feed_id;
+        state.accepted_channels = accepted_channels;
         state.latest_timestamp = 0;
         state.latest_price = 0;
+        state.latest_exponent = None;
+        state.max_deviation_bps_per_second = max_deviation_bps_per_second;
+        state.deviation_mode = deviation_mode;
+        state.pending_timestamp = 0;
+        state.pending_price = 0;
+        state.authority = ctx.accounts.payer.key();
+        state.paused = false;
+        Ok(())
+    }
+
+    /// Pauses or resumes updates. The stored price can still be read while paused.
+    pub fn set_paused(ctx: Context<SetPaused>, paused: bool) -> Result<()> {
+        ctx.accounts.state.paused = paused;
+        Ok(())
+    }
+
+    /// Returns the latest price if it's at most `max_age_us` old. The price of a paused
+    /// price feed is returned with `PriceResult::paused` set, the caller decides whether
+    /// to use it.
+    pub fn get_price(ctx: Context<GetPrice>, max_age_us: u64) -> Result<PriceResult> {
+        let state = &ctx.accounts.state;
+        require!(state.latest_timestamp != 0, ErrorCode::MissingPrice);
+        let now_us = u64::try_from(Clock::get()?.unix_timestamp)
+            .unwrap_or(0)
+            .saturating_mul(1_000_000);
+        require!(
+            now_us.saturating_sub(state.latest_timestamp) <= max_age_us,
+            ErrorCode::PriceStale
+        );
+        Ok(PriceResult {
+            price_feed_id: state.price_feed_id,
+            timestamp_us: state.latest_timestamp,
+            price: state.latest_price,
+            exponent: state.latest_exponent,
+            paused: state.paused,
+        })
+    }
+
+    /// Changes the deviation limit set by `initialize`.
+    pub fn set_deviation_limit(
+        ctx: Context<SetDeviationLimit>,
+        max_deviation_bps_per_second: u32,
+        deviation_mode: DeviationMode,
+    ) -> Result<()> {
+        let state = &mut ctx.accounts.state;
+        state.max_deviation_bps_per_second = max_deviation_bps_per_second;
+        state.deviation_mode = deviation_mode;
         Ok(())
     }
 
     pub fn update_ecdsa(ctx: Context<UpdateEcdsa>, pyth_message: Vec<u8>) -> Result<()> {
+        // Check before paying for the verification
+        require!(!ctx.accounts.state.paused, ErrorCode::Paused);
+
         // Verify ECDSA signature
         let cpi_accounts = pyth_lazer_solana_contract::cpi::accounts::VerifyEcdsaMessage {
             payer: ctx.accounts.payer.to_account_info(),
@@ -41,7 +102,7 @@ pub mod solana_anchor {
         let data = PayloadData::deserialize_slice_le(&pyth_message.payload)
             .map_err(|_| ErrorCode::InvalidPayload)?;
 
-        apply_update(&mut ctx.accounts.state, &data)?;
+        apply_update(&mut ctx.accounts.state, &data, VerificationPath::Ecdsa)?;
 
         Ok(())
     }
@@ -64,6 +125,28 @@ pub struct Initialize<'info> {
     pub system_program: Program<'info, System>,
 }
 
+#[derive(Accounts)]
+pub struct SetPaused<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(mut, seeds = [b"data"], bump, has_one = authority @ ErrorCode::Unauthorized)]
+    pub state: Account<'info, State>,
+}
+
+#[derive(Accounts)]
+pub struct GetPrice<'info> {
+    #[account(seeds = [b"data"], bump)]
+    pub state: Account<'info, State>,
+}
+
+#[derive(Accounts)]
+pub struct SetDeviationLimit<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(mut, seeds = [b"data"], bump, has_one = authority @ ErrorCode::Unauthorized)]
+    pub state: Account<'info, State>,
+}
+
 #[derive(Accounts)]
 pub struct UpdateEcdsa<'info> {
     #[account(mut)]
@@ -91,6 +174,70 @@ pub struct State {
     pub price_feed_id: u32,
     pub latest_timestamp: u64,
     pub latest_price: i64,
+    /// Exponent of the latest price, if its update contained one.
+    pub latest_exponent: Option<i16>,
+    pub accepted_channels: u32,
+    pub max_deviation_bps_per_second: u32,
+    pub deviation_mode: DeviationMode,
+    /// Timestamp of the quarantined update, or 0 if there is none.
+    pub pending_timestamp: u64,
+    /// Price of the quarantined update.
+    pub pending_price: i64,
+    /// Account that is allowed to pause updates and to change the deviation limit,
+    /// the payer of `initialize`.
+    pub authority: Pubkey,
+    /// Whether updates are paused by the authority. Programs reading the price
+    /// should stop using it while it's set, `get_price` reports it.
+    pub paused: bool,
+}
+
+/// Return value of `get_price`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PriceResult {
+    pub price_feed_id: u32,
+    /// Timestamp of the price, in microseconds since the Unix epoch.
+    pub timestamp_us: u64,
+    pub price: i64,
+    pub exponent: Option<i16>,
+    /// Whether updates are paused by the authority.
+    pub paused: bool,
+}
+
+/// Handling of updates that move the price further than `State::max_deviation_bps_per_second`
+/// allows.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DeviationMode {
+    /// Fail the instruction with `ErrorCode::DeviationExceeded`.
+    Reject,
+    /// Keep the update in the pending slot of the state instead of applying it.
+    /// It's confirmed by a later update that is within the allowed deviation from it.
+    Quarantine,
+}
+
+/// How the signature of an update was verified.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VerificationPath {
+    /// Solana message verified with an ed25519 instruction.
+    Ed25519,
+    /// LE-ECDSA message verified by the Pyth Lazer program.
+    Ecdsa,
+}
+
+/// Emitted for every price stored by an update. Quarantined updates are not emitted.
+#[event]
+pub struct PriceUpdated {
+    pub price_feed_id: u32,
+    pub price: i64,
+    /// `None` if the update doesn't contain the exponent.
+    pub exponent: Option<i16>,
+    pub timestamp_us: u64,
+    pub channel_id: u8,
+    pub verification_path: VerificationPath,
+}
+
+/// Returns the bit representing `channel_id` in `State::accepted_channels`.
+pub fn channel_bit(channel_id: ChannelId) -> u32 {
+    1u32.checked_shl(channel_id.0.into()).unwrap_or(0)
 }
 
 #[error_code]
@@ -107,16 +254,30 @@ pub enum ErrorCode {
     InvalidPayloadProperty,
     #[msg("Invalid payload timestamp")]
     InvalidPayloadTimestamp,
+    #[msg("Price deviation exceeded")]
+    DeviationExceeded,
+    #[msg("Signer is not the authority")]
+    Unauthorized,
+    #[msg("Updates are paused")]
+    Paused,
+    #[msg("The price feed has no price yet")]
+    MissingPrice,
+    #[msg("The price is older than the maximum age")]
+    PriceStale,
 }
 
-fn apply_update(state: &mut Account<State>, data: &PayloadData) -> Result<()> {
-    // Check the channel is what we expect
-    if data.channel_id != FIXED_RATE_200 {
+fn apply_update(
+    state: &mut Account<State>,
+    data: &PayloadData,
+    verification_path: VerificationPath,
+) -> Result<()> {
+    // Check the channel is one we accept
+    if state.accepted_channels & channel_bit(data.channel_id) == 0 {
         return Err(ErrorCode::InvalidChannel.into());
     }
 
     // Check if the timestamp is greater than the current timestamp
-    if data.timestamp_us.as_micros() <= state.latest_timestamp {
+    if data.timestamp_us.as_micros() <= state.latest_timestamp.max(state.pending_timestamp) {
         return Err(ErrorCode::InvalidPayloadTimestamp.into());
     }
 
@@ -130,18 +291,142 @@ fn apply_update(state: &mut Account<State>, data: &PayloadData) -> Result<()> {
         return Err(ErrorCode::InvalidPayloadFeedId.into());
     }
 
-    // Check the payload has a single price property
-    if data.feeds[0].properties.len() != 1 {
-        return Err(ErrorCode::InvalidPayloadProperty.into());
+    // Check the payload has a price and at most its exponent besides, in any order
+    let mut price = None;
+    let mut exponent = None;
+    for property in &data.feeds[0].properties {
+        match *property {
+            PayloadPropertyValue::Price(Some(value)) if price.is_none() => price = Some(value),
+            PayloadPropertyValue::Exponent(value) if exponent.is_none() => exponent = Some(value),
+            _ => return Err(ErrorCode::InvalidPayloadProperty.into()),
+        }
     }
-
-    // Check the price property is a price
-    let PayloadPropertyValue::Price(Some(price)) = data.feeds[0].properties[0] else {
+    let Some(price) = price else {
         return Err(ErrorCode::InvalidPayloadProperty.into());
     };
 
-    state.latest_price = price.into_inner().into();
-    state.latest_timestamp = data.timestamp_us.as_micros();
+    // Check the price doesn't move too fast, there is nothing to compare the first price to
+    let update = (data.timestamp_us.as_micros(), price.into_inner().get());
+    if state.latest_timestamp != 0
+        && !is_within_deviation(
+            state.max_deviation_bps_per_second,
+            (state.latest_timestamp, state.latest_price),
+            update,
+        )
+    {
+        let is_confirmed = state.pending_timestamp != 0
+            && is_within_deviation(
+                state.max_deviation_bps_per_second,
+                (state.pending_timestamp, state.pending_price),
+                update,
+            );
+        match state.deviation_mode {
+            DeviationMode::Reject => return Err(ErrorCode::DeviationExceeded.into()),
+            DeviationMode::Quarantine if !is_confirmed => {
+                state.pending_timestamp = update.0;
+                state.pending_price = update.1;
+                return Ok(());
+            }
+            DeviationMode::Quarantine => {}
+        }
+    }
+    state.pending_timestamp = 0;
+    state.pending_price = 0;
+
+    state.latest_price = update.1;
+    state.latest_exponent = exponent;
+    state.latest_timestamp = update.0;
+
+    emit!(PriceUpdated {
+        price_feed_id: state.price_feed_id,
+        price: state.latest_price,
+        exponent: state.latest_exponent,
+        timestamp_us: state.latest_timestamp,
+        channel_id: data.channel_id.0,
+        verification_path,
+    });
 
     Ok(())
 }
+
+/// Used by `apply_update` to compare an update with the stored price, then with the
+/// quarantined one. Both are `(timestamp_us, price)` pairs and `max_bps_per_second` is
+/// `State::max_deviation_bps_per_second`, so the allowed move grows with the time between
+/// the two prices and 0 allows any move.
+fn is_within_deviation(max_bps_per_second: u32, previous: (u64, i64), next: (u64, i64)) -> bool {
+    if max_bps_per_second == 0 {
+        return true;
+    }
+    let elapsed_us = next.0.saturating_sub(previous.0);
+    let change = (i128::from(next.1) - i128::from(previous.1)).unsigned_abs();
+    // change / |previous price| * 10_000 <= max_bps_per_second * elapsed_us / 1_000_000
+    change * 10_000 * 1_000_000
+        <= u128::from(max_bps_per_second)
+            .saturating_mul(elapsed_us.into())
+            .saturating_mul(previous.1.unsigned_abs().into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::is_within_deviation;
+
+    /// Checks a move from `previous` at 1 second to `next` after `elapsed_us` with a limit
+    /// of 100 basis points per second.
+    fn is_within_1_percent_per_second(previous: i64, elapsed_us: u64, next: i64) -> bool {
+        is_within_deviation(100, (1_000_000, previous), (1_000_000 + elapsed_us, next))
+    }
+
+    #[test]
+    fn test_deviation_disabled() {
+        assert!(is_within_deviation(0, (1_000_000, 100), (1_000_000, 0)));
+        assert!(is_within_deviation(0, (1_000_000, 100), (0, i64::MIN)));
+    }
+
+    #[test]
+    fn test_deviation_scales_with_elapsed_time() {
+        assert!(is_within_1_percent_per_second(10_000, 1_000_000, 10_100));
+        assert!(!is_within_1_percent_per_second(10_000, 1_000_000, 10_101));
+        assert!(is_within_1_percent_per_second(10_000, 2_000_000, 9_800));
+        assert!(!is_within_1_percent_per_second(10_000, 2_000_000, 9_799));
+        assert!(is_within_1_percent_per_second(10_000, 500_000, 10_050));
+        assert!(!is_within_1_percent_per_second(10_000, 500_000, 10_051));
+    }
+
+    #[test]
+    fn test_deviation_without_elapsed_time() {
+        assert!(is_within_1_percent_per_second(10_000, 0, 10_000));
+        assert!(!is_within_1_percent_per_second(10_000, 0, 10_001));
+        // An earlier update allows no move either.
+        assert!(!is_within_deviation(
+            100,
+            (2_000_000, 10_000),
+            (1_000_000, 10_001)
+        ));
+    }
+
+    #[test]
+    fn test_deviation_relative_to_previous_price() {
+        // The limit is relative to the magnitude of the previous price, even if it's negative.
+        assert!(is_within_1_percent_per_second(-10_000, 1_000_000, -9_900));
+        assert!(!is_within_1_percent_per_second(-10_000, 1_000_000, -9_899));
+        // Nothing but zero is within the limit of a zero price.
+        assert!(is_within_1_percent_per_second(0, 1_000_000, 0));
+        assert!(!is_within_1_percent_per_second(0, 1_000_000, 1));
+    }
+
+    #[test]
+    fn test_deviation_extreme_values() {
+        // The computation saturates instead of overflowing.
+        assert!(is_within_deviation(
+            u32::MAX,
+            (0, i64::MAX),
+            (u64::MAX, i64::MIN)
+        ));
+        assert!(!is_within_1_percent_per_second(1, u64::MAX / 2, i64::MAX));
+        assert!(!is_within_1_percent_per_second(
+            i64::MIN,
+            1_000_000,
+            i64::MAX
+        ));
+    }
+}
@@ -57,23 +57,173 @@ describe("solana-anchor", () => {
     "hex"
   );
 
+  // Updates of price feed 1 on channel 3 after `messageEcdsa`, signed by the test key
+  // whose secp256k1 secret key is 32 bytes of 0x42
+  const testSigner = Buffer.from("17c5185167401ed00cf5f5b2fc97d9bbfdb7d025", "hex");
+  const testMessages = {
+    // 1 second later, +50%: 14297028766893
+    jump: Buffer.from(
+      "e4bd474d31efd0f808ee5687fb7f012162f44f3772a468d3e9aa6ffbe07535064bde552c07a074303422a3abdc3fd29b88dc7cf5af87a76c6be64d45f79b39911aa97f3e001c0075d3c7932847757ed63306000301010000000100ad2c52c9000d0000",
+      "hex"
+    ),
+    // 1 second later, +0.5%: 9579009273818, with exponent -8
+    small: Buffer.from(
+      "e4bd474dd6c578f38c3d783b1c768d6d36df7da32fe4ddeed7603832a69013d06e22b7bf031ddf5855f58a93f88204c436f58b321d47dc51eb251d44d037b730f0ca74da001f0075d3c7932847757ed63306000301010000000200daef7149b608000004f8ff",
+      "hex"
+    ),
+    // 2 seconds later, +50% from `small`: 14368513910727
+    quarantined: Buffer.from(
+      "e4bd474d0a5a94af019e1e1d0b43d54552063ecfbc27120642015882e54677d3beb5879c45ed1d8e5ea109ac93843e9d0ba7e07929a9fdd534a8e42389aaed8518b167d8001c0075d3c7936889847ed63306000301010000000100c7e72a6e110d0000",
+      "hex"
+    ),
+    // 3 seconds later, +0.5% from `quarantined`: 14440356480280
+    confirming: Buffer.from(
+      "e4bd474d69dedfc29067a47bcb93dfab84c2aee7a12164351f4d05abb63e71457b5cf017670636a24cfa4bb1546730a1bc77bd7d7b0f9429437e197cc286d97c184a0858001c0075d3c793a8cb937ed6330600030101000000010018855128220d0000",
+      "hex"
+    ),
+  };
+
+  let treasury: Keypair;
+  let pythLazerContract: Program<PythLazerSolanaContract>;
+  const updateEcdsa = (message: Buffer) =>
+    program.methods.updateEcdsa(message).accounts({
+      payer: provider.wallet.publicKey,
+      pythTreasury: treasury.publicKey,
+    }).rpc({ commitment: "confirmed" });
+  const getEvents = async (signature: string) => {
+    const tx = await provider.connection.getTransaction(signature, {
+      commitment: "confirmed",
+      maxSupportedTransactionVersion: 0,
+    });
+    const eventParser = new anchor.EventParser(program.programId, new anchor.BorshCoder(program.idl));
+    return [...eventParser.parseLogs(tx.meta.logMessages)];
+  };
+  const getState = async () => (await program.account.state.all())[0].account; // There's only one state account
+
   it("Initializes the program and tests updates with ECDSA message", async () => {
-    const { treasury } = await setupPythLazerContract(provider);
-    // Initialize Solana Anchor program with price feed id 1
-    await program.methods.initialize(1).accounts({
+    ({ treasury, pythLazerContract } = await setupPythLazerContract(provider));
+    // Initialize Solana Anchor program with price feed id 1, accepting updates
+    // from the fixed_rate@200ms channel (channel id 3) without a deviation limit
+    await program.methods.initialize(1, 1 << 3, 0, { reject: {} }).accounts({
       payer: provider.wallet.publicKey,
     }).rpc();
 
+    // Updates fail while paused, and only the authority can pause
+    const other = Keypair.generate();
+    await assert.rejects(
+      program.methods.setPaused(true).accounts({
+        authority: other.publicKey,
+      }).signers([other]).rpc(),
+      (err: anchor.AnchorError) => err.error.errorCode.code === "Unauthorized"
+    );
+    await program.methods.setPaused(true).accounts({
+      authority: provider.wallet.publicKey,
+    }).rpc();
+    assert.equal((await program.account.state.all())[0].account.paused, true);
+    await assert.rejects(
+      program.methods.updateEcdsa(messageEcdsa).accounts({
+        payer: provider.wallet.publicKey,
+        pythTreasury: treasury.publicKey,
+      }).rpc(),
+      (err: anchor.AnchorError) => err.error.errorCode.code === "Paused"
+    );
+    await program.methods.setPaused(false).accounts({
+      authority: provider.wallet.publicKey,
+    }).rpc();
+
     // Update with ECDSA message
-    await program.methods.updateEcdsa(messageEcdsa).accounts({
+    const signature = await program.methods.updateEcdsa(messageEcdsa).accounts({
       payer: provider.wallet.publicKey,
       pythTreasury: treasury.publicKey,
-    }).rpc();
+    }).rpc({ commitment: "confirmed" });
+
+    // The stored price is emitted as an event
+    const events = await getEvents(signature);
+    assert.equal(events.length, 1);
+    assert.equal(events[0].name, "priceUpdated");
+    assert.equal(events[0].data.priceFeedId.toString(), "1");
+    assert.equal(events[0].data.price.toString(), "9531352511262");
+    // The fixture contains no exponent
+    assert.equal(events[0].data.exponent, null);
+    assert.equal(events[0].data.timestampUs.toString(), "1745846196897000");
+    assert.deepEqual(events[0].data.verificationPath, { ecdsa: {} });
 
     // Get the state
     const state = (await program.account.state.all())[0].account; // There's only one state account
     assert.equal(state.priceFeedId.toString(), "1");
     assert.equal(state.latestTimestamp.toString(), "1745846196897000");
     assert.equal(state.latestPrice.toString(), "9531352511262");
+
+    // The price stays readable while paused and reports the pause. The fixture is older
+    // than the clock of the validator, hence the maximum age.
+    const maxAgeUs = new BN(Date.now()).mul(new BN(1000));
+    await program.methods.setPaused(true).accounts({
+      authority: provider.wallet.publicKey,
+    }).rpc();
+    const price = await program.methods.getPrice(maxAgeUs).view();
+    assert.equal(price.priceFeedId.toString(), "1");
+    assert.equal(price.timestampUs.toString(), "1745846196897000");
+    assert.equal(price.price.toString(), "9531352511262");
+    assert.equal(price.paused, true);
+    await program.methods.setPaused(false).accounts({
+      authority: provider.wallet.publicKey,
+    }).rpc();
+    assert.equal((await program.methods.getPrice(maxAgeUs).view()).paused, false);
+    // The price is too old for a maximum age of 1 second
+    await assert.rejects(program.methods.getPrice(new BN(1_000_000)).view());
+  });
+
+  it("Rejects updates exceeding the deviation limit", async () => {
+    await pythLazerContract.methods.updateEcdsaSigner(Array.from(testSigner), new BN(2376995919)).accounts({
+      payer: provider.wallet.publicKey,
+    }).rpc();
+
+    // Only the authority can change the limit
+    const other = Keypair.generate();
+    await assert.rejects(
+      program.methods.setDeviationLimit(100, { reject: {} }).accounts({
+        authority: other.publicKey,
+      }).signers([other]).rpc(),
+      (err: anchor.AnchorError) => err.error.errorCode.code === "Unauthorized"
+    );
+    // 1% per second
+    await program.methods.setDeviationLimit(100, { reject: {} }).accounts({
+      authority: provider.wallet.publicKey,
+    }).rpc();
+
+    await assert.rejects(
+      updateEcdsa(testMessages.jump),
+      (err: anchor.AnchorError) => err.error.errorCode.code === "DeviationExceeded"
+    );
+    let state = await getState();
+    assert.equal(state.latestPrice.toString(), "9531352511262");
+
+    const events = await getEvents(await updateEcdsa(testMessages.small));
+    assert.equal(events[0].data.price.toString(), "9579009273818");
+    assert.equal(events[0].data.exponent, -8);
+    state = await getState();
+    assert.equal(state.latestTimestamp.toString(), "1745846197897000");
+    assert.equal(state.latestPrice.toString(), "9579009273818");
+    assert.equal(state.latestExponent, -8);
+  });
+
+  it("Quarantines updates exceeding the deviation limit until they are confirmed", async () => {
+    await program.methods.setDeviationLimit(100, { quarantine: {} }).accounts({
+      authority: provider.wallet.publicKey,
+    }).rpc();
+
+    // The jump is held back instead of being applied
+    await updateEcdsa(testMessages.quarantined);
+    let state = await getState();
+    assert.equal(state.latestPrice.toString(), "9579009273818");
+    assert.equal(state.pendingTimestamp.toString(), "1745846198897000");
+    assert.equal(state.pendingPrice.toString(), "14368513910727");
+
+    // A later update close to the quarantined price confirms the jump
+    await updateEcdsa(testMessages.confirming);
+    state = await getState();
+    assert.equal(state.latestTimestamp.toString(), "1745846199897000");
+    assert.equal(state.latestPrice.toString(), "14440356480280");
+    assert.equal(state.pendingTimestamp.toString(), "0");
   });
 });
@@ -0,0 +1,320 @@
+//! The `inspect` command: decodes a signed update and predicts whether the example program
+//! would apply it to a price feed.
+
+use {
+    crate::{relay::feed_price, Client, UpdateSource},
+    anyhow::Context as _,
+    clap::Args,
+    num_traits::FromPrimitive,
+    pyth_lazer_solana_contract::{
+        protocol::{
+            message::{LeEcdsaMessage, SolanaMessage},
+            payload::{PayloadData, PayloadPropertyValue},
+            router::{Channel, ChannelId, FixedRate},
+        },
+        EvmAddress,
+    },
+    pyth_lazer_solana_example::{
+        find_updater_allowlist_pda, Error, UpdaterAllowlist, MAX_FUTURE_SKEW_US,
+    },
+    serde_json::{json, Value},
+    solana_sdk::{
+        account::from_account, clock::Clock, keccak, program_error::ProgramError, pubkey::Pubkey,
+        secp256k1_recover::secp256k1_recover, signature::Signature, signer::Signer as _, sysvar,
+    },
+};
+
+#[derive(Args)]
+pub struct InspectArgs {
+    #[command(flatten)]
+    update: UpdateSource,
+    /// Also check the update against the current state of this price feed, as if it was
+    /// applied with the `update` command.
+    #[arg(long)]
+    feed_id: Option<u32>,
+    /// Print JSON instead of text.
+    #[arg(long)]
+    json: bool,
+}
+
+/// A decoded signed update.
+struct Inspected {
+    format: &'static str,
+    signature: [u8; 64],
+    signer: Signer,
+    payload: PayloadData,
+}
+
+enum Signer {
+    /// Public key of a Solana message and whether the signature matches it.
+    Ed25519 { public_key: Pubkey, is_valid: bool },
+    /// Address recovered from the signature of an LE-ECDSA message, or `None` if the
+    /// signature is invalid.
+    Ecdsa { address: Option<EvmAddress> },
+}
+
+/// What the example program would do with the update.
+enum Outcome {
+    Applied,
+    Quarantined,
+    Rejected(String),
+}
+
+impl Outcome {
+    fn rejected(err: ProgramError) -> Self {
+        let reason = match err {
+            ProgramError::Custom(code) => {
+                Error::from_u32(code).map_or_else(|| err.to_string(), |err| err.to_string())
+            }
+            err => err.to_string(),
+        };
+        Outcome::Rejected(reason)
+    }
+}
+
+pub fn run(client: &Client, args: InspectArgs) -> anyhow::Result<()> {
+    let inspected = decode(&args.update.read()?)?;
+    let outcome = args
+        .feed_id
+        .map(|price_feed_id| {
+            Ok::<_, anyhow::Error>((price_feed_id, check(client, &inspected, price_feed_id)?))
+        })
+        .transpose()?;
+    if args.json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&to_json(&inspected, outcome.as_ref()))?
+        );
+    } else {
+        print_text(&inspected, outcome.as_ref());
+    }
+    Ok(())
+}
+
+fn decode(message: &[u8]) -> anyhow::Result<Inspected> {
+    let (format, signature, signer, payload) = match SolanaMessage::deserialize_slice(message) {
+        Ok(message) => {
+            let is_valid =
+                Signature::from(message.signature).verify(&message.public_key, &message.payload);
+            let signer = Signer::Ed25519 {
+                public_key: Pubkey::from(message.public_key),
+                is_valid,
+            };
+            ("solana", message.signature, signer, message.payload)
+        }
+        Err(_) => {
+            let message = LeEcdsaMessage::deserialize_slice(message)
+                .context("update is neither a Solana nor an LE-ECDSA message")?;
+            // The same recovery as in the Pyth Lazer program.
+            let address = secp256k1_recover(
+                &keccak::hash(&message.payload).0,
+                message.recovery_id,
+                &message.signature,
+            )
+            .ok()
+            .map(|public_key| {
+                let mut address = EvmAddress::default();
+                address.copy_from_slice(&keccak::hash(&public_key.0).0[12..]);
+                address
+            });
+            let signer = Signer::Ecdsa { address };
+            ("le_ecdsa", message.signature, signer, message.payload)
+        }
+    };
+    let payload = PayloadData::deserialize_slice_le(&payload).context("invalid payload")?;
+    Ok(Inspected {
+        format,
+        signature,
+        signer,
+        payload,
+    })
+}
+
+/// Replays the checks of the example program, in the same order, for an `Update` of
+/// `price_feed_id` paid by the client keypair.
+fn check(client: &Client, inspected: &Inspected, price_feed_id: u32) -> anyhow::Result<Outcome> {
+    let state = client.state(price_feed_id)?;
+    let storage = client.pyth_storage()?;
+    let clock: Clock = from_account(&client.rpc.get_account(&sysvar::clock::ID)?)
+        .context("invalid clock sysvar")?;
+
+    if state.paused != 0 {
+        return Ok(Outcome::rejected(Error::Paused.into()));
+    }
+    if state.updater_allowlist_enabled != 0 {
+        let (allowlist_pda_key, _) = find_updater_allowlist_pda(&client.program_id, price_feed_id);
+        let allowlist_data = client
+            .rpc
+            .get_account_data(&allowlist_pda_key)
+            .context("failed to fetch the updater allowlist account")?;
+        let allowlist =
+            UpdaterAllowlist::load(&allowlist_data).context("invalid updater allowlist account")?;
+        if !allowlist.updaters().contains(&client.keypair.pubkey()) {
+            return Ok(Outcome::rejected(Error::UnauthorizedUpdater.into()));
+        }
+    }
+
+    let is_trusted = match &inspected.signer {
+        Signer::Ed25519 {
+            public_key,
+            is_valid,
+        } => {
+            *is_valid
+                && storage.initialized_trusted_signers().iter().any(|signer| {
+                    signer.pubkey == *public_key && signer.expires_at > clock.unix_timestamp
+                })
+        }
+        Signer::Ecdsa { address } => address.map_or(false, |address| {
+            storage
+                .initialized_trusted_ecdsa_signers()
+                .iter()
+                .any(|signer| signer.pubkey == address && signer.expires_at > clock.unix_timestamp)
+        }),
+    };
+    if !is_trusted {
+        return Ok(Outcome::Rejected(
+            "signer is not trusted by the Pyth Lazer program".to_string(),
+        ));
+    }
+
+    let payload = &inspected.payload;
+    let now_us = u64::try_from(clock.unix_timestamp)
+        .unwrap_or(0)
+        .saturating_mul(1_000_000);
+    if payload.timestamp_us.0 > now_us.saturating_add(MAX_FUTURE_SKEW_US) {
+        return Ok(Outcome::rejected(Error::TimestampInFuture.into()));
+    }
+    let age_us = now_us.saturating_sub(payload.timestamp_us.0);
+
+    // `update` passes the data account of this price feed only and rejects other feeds.
+    let mut outcome = Outcome::rejected(Error::WrongPriceFeed.into());
+    for feed in &payload.feeds {
+        if feed.feed_id.0 != price_feed_id {
+            return Ok(Outcome::rejected(Error::WrongPriceFeed.into()));
+        }
+        outcome = match state.check_update(payload, age_us, feed_price(&feed.properties)) {
+            Ok(true) => Outcome::Applied,
+            Ok(false) => Outcome::Quarantined,
+            Err(err) => return Ok(Outcome::rejected(err)),
+        };
+    }
+    Ok(outcome)
+}
+
+fn print_text(inspected: &Inspected, outcome: Option<&(u32, Outcome)>) {
+    println!("format: {}", inspected.format);
+    println!("signature: {}", hex::encode(inspected.signature));
+    match &inspected.signer {
+        Signer::Ed25519 {
+            public_key,
+            is_valid,
+        } => {
+            let validity = if *is_valid { "valid" } else { "invalid" };
+            println!("public key: {public_key} (signature {validity})");
+        }
+        Signer::Ecdsa {
+            address: Some(address),
+        } => println!("recovered address: 0x{}", hex::encode(address)),
+        Signer::Ecdsa { address: None } => println!("recovered address: none (invalid signature)"),
+    }
+
+    let payload = &inspected.payload;
+    println!("timestamp: {} us", payload.timestamp_us.0);
+    match channel_name(payload.channel_id) {
+        Some(name) => println!("channel: {} ({name})", payload.channel_id.0),
+        None => println!("channel: {}", payload.channel_id.0),
+    }
+    for feed in &payload.feeds {
+        println!("feed {}:", feed.feed_id.0);
+        for property in &feed.properties {
+            let (name, value) = property_json(property);
+            println!("  {name}: {value}");
+        }
+    }
+
+    if let Some((price_feed_id, outcome)) = outcome {
+        match outcome {
+            Outcome::Applied => println!("price feed {price_feed_id}: would be applied"),
+            Outcome::Quarantined => println!("price feed {price_feed_id}: would be quarantined"),
+            Outcome::Rejected(reason) => {
+                println!("price feed {price_feed_id}: would be rejected: {reason}")
+            }
+        }
+    }
+}
+
+fn to_json(inspected: &Inspected, outcome: Option<&(u32, Outcome)>) -> Value {
+    let payload = &inspected.payload;
+    let mut value = json!({
+        "format": inspected.format,
+        "signature": hex::encode(inspected.signature),
+        "payload": {
+            "timestamp_us": payload.timestamp_us.0,
+            "channel_id": payload.channel_id.0,
+            "channel": channel_name(payload.channel_id),
+            "feeds": payload.feeds.iter().map(|feed| json!({
+                "feed_id": feed.feed_id.0,
+                "properties": feed.properties.iter().map(property_json).collect::<serde_json::Map<_, _>>(),
+            })).collect::<Vec<_>>(),
+        },
+    });
+    match &inspected.signer {
+        Signer::Ed25519 {
+            public_key,
+            is_valid,
+        } => {
+            value["public_key"] = json!(public_key.to_string());
+            value["signature_valid"] = json!(is_valid);
+        }
+        Signer::Ecdsa { address } => {
+            value["address"] = json!(address.map(|address| format!("0x{}", hex::encode(address))));
+        }
+    }
+    if let Some((price_feed_id, outcome)) = outcome {
+        let (result, reason) = match outcome {
+            Outcome::Applied => ("applied", None),
+            Outcome::Quarantined => ("quarantined", None),
+            Outcome::Rejected(reason) => ("rejected", Some(reason)),
+        };
+        value["check"] = json!({
+            "price_feed_id": price_feed_id,
+            "outcome": result,
+            "reason": reason,
+        });
+    }
+    value
+}
+
+/// Returns the name of a property and its value, `null` if the value is absent.
+fn property_json(property: &PayloadPropertyValue) -> (String, Value) {
+    let (name, value) = match property {
+        PayloadPropertyValue::Price(price) => ("price", json!(price.map(|price| price.0))),
+        PayloadPropertyValue::BestBidPrice(price) => {
+            ("best_bid_price", json!(price.map(|price| price.0)))
+        }
+        PayloadPropertyValue::BestAskPrice(price) => {
+            ("best_ask_price", json!(price.map(|price| price.0)))
+        }
+        PayloadPropertyValue::PublisherCount(count) => ("publisher_count", json!(count)),
+        PayloadPropertyValue::Exponent(exponent) => ("exponent", json!(exponent)),
+        PayloadPropertyValue::Confidence(confidence) => (
+            "confidence",
+            json!(confidence.map(|confidence| confidence.0)),
+        ),
+        PayloadPropertyValue::FundingRate(rate) => ("funding_rate", json!(rate.map(|rate| rate.0))),
+        PayloadPropertyValue::FundingTimestamp(timestamp) => (
+            "funding_timestamp_us",
+            json!(timestamp.map(|timestamp| timestamp.0)),
+        ),
+    };
+    (name.to_string(), value)
+}
+
+/// Returns the name of the channel with `channel_id`, if it's known.
+fn channel_name(channel_id: ChannelId) -> Option<String> {
+    FixedRate::ALL
+        .into_iter()
+        .map(Channel::FixedRate)
+        .find(|channel| channel.id() == channel_id)
+        .map(|channel| channel.to_string())
+}
@@ -0,0 +1,545 @@
+mod inspect;
+mod read;
+mod relay;
+
+use {
+    anchor_lang::AccountDeserialize,
+    anyhow::Context as _,
+    clap::{Args, Parser, Subcommand, ValueEnum},
+    num_traits::FromPrimitive,
+    pyth_lazer_solana_contract::protocol::{
+        message::{format_magics_le::SOLANA_FORMAT_MAGIC, LeEcdsaMessage, SolanaMessage},
+        payload::PayloadData,
+        router::ChannelId,
+    },
+    pyth_lazer_solana_example::{
+        channel_bit, events::parse_price_update_events, find_data_pda, instructions, DeviationMode,
+        Error, InitializeArgs, State, UnmatchedFeedMode, UpdateArgs, VerificationPath,
+    },
+    solana_client::{
+        client_error::{ClientError, ClientErrorKind},
+        rpc_client::RpcClient,
+        rpc_config::RpcTransactionConfig,
+        rpc_request::{RpcError, RpcResponseErrorData},
+    },
+    solana_sdk::{
+        hash::Hash,
+        instruction::Instruction,
+        message::Message,
+        packet::PACKET_DATA_SIZE,
+        pubkey::Pubkey,
+        signature::{read_keypair_file, Keypair, Signature},
+        signer::Signer,
+        transaction::Transaction,
+    },
+    solana_transaction_status::UiTransactionEncoding,
+    std::{fs, path::PathBuf},
+};
+
+/// Client of the Pyth Lazer example program. Options can also be set with the environment
+/// variables shown in the help.
+// The options are global so that they can follow the command. Clap doesn't allow global options
+// to be required, `main` checks them instead.
+#[derive(Parser)]
+struct Cli {
+    /// URL of the Solana RPC endpoint. Required.
+    #[arg(long, env = "SOLANA_RPC_URL", global = true)]
+    rpc_url: Option<String>,
+    /// Keypair file of the payer, which is also the authority of initialized price feeds.
+    /// Required.
+    #[arg(long, env = "SOLANA_KEYPAIR_FILE", global = true)]
+    keypair: Option<PathBuf>,
+    /// Address of the example program. Required.
+    #[arg(long, env = "EXAMPLE_PROGRAM_PUBKEY", global = true)]
+    program_id: Option<Pubkey>,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Create the data account of a price feed.
+    Init {
+        #[command(flatten)]
+        feed: FeedArg,
+        /// Maximum age of an accepted update, in microseconds.
+        #[arg(long, env = "MAX_AGE_US")]
+        max_age_us: u64,
+        /// Comma-separated IDs of the channels that updates are accepted from, e.g. `1,3`.
+        #[arg(
+            long,
+            env = "ACCEPTED_CHANNEL_IDS",
+            value_delimiter = ',',
+            required = true
+        )]
+        accepted_channel_ids: Vec<u8>,
+        /// Maximum price move of an update in basis points per second, 0 disables the check.
+        #[arg(long, env = "MAX_DEVIATION_BPS_PER_SECOND", default_value_t = 0)]
+        max_deviation_bps_per_second: u32,
+        /// What happens to updates exceeding the maximum deviation.
+        #[arg(long, env = "DEVIATION_MODE", value_enum, default_value_t = DeviationModeArg::Reject)]
+        deviation_mode: DeviationModeArg,
+    },
+    /// Apply a signed update in the Solana or the LE-ECDSA format to a price feed.
+    Update {
+        #[command(flatten)]
+        feed: FeedArg,
+        #[command(flatten)]
+        update: UpdateSource,
+    },
+    /// Apply a signed update in the LE-ECDSA format to a price feed with `UpdateEcdsa`,
+    /// which is verified by the Pyth Lazer program without an ed25519 instruction.
+    UpdateEcdsa {
+        #[command(flatten)]
+        feed: FeedArg,
+        #[command(flatten)]
+        update: UpdateSource,
+    },
+    /// Apply signed updates in the Solana format to the price feeds they contain,
+    /// packing as many updates as fit into each transaction.
+    UpdateBatch {
+        /// Comma-separated hex-encoded updates.
+        #[arg(
+            long,
+            env = "LAZER_UPDATES_HEX",
+            value_delimiter = ',',
+            required = true
+        )]
+        updates_hex: Vec<String>,
+    },
+    /// Print the state of a price feed, or of all price feeds.
+    Read(read::ReadArgs),
+    /// Replace the updater allowlist of a price feed. It's disabled if no updaters are given.
+    SetUpdaterAllowlist {
+        #[command(flatten)]
+        feed: FeedArg,
+        /// Comma-separated public keys of the allowed updaters.
+        #[arg(long, env = "UPDATERS", value_delimiter = ',')]
+        updaters: Vec<Pubkey>,
+    },
+    /// Subscribe to Pyth Lazer and apply updates whenever prices move or a heartbeat
+    /// interval elapses. Runs until interrupted.
+    Relay(relay::RelayArgs),
+    /// Pause updates of a price feed.
+    Pause {
+        #[command(flatten)]
+        feed: FeedArg,
+    },
+    /// Resume updates of a price feed.
+    Unpause {
+        #[command(flatten)]
+        feed: FeedArg,
+    },
+    /// Decode a signed update, and optionally check whether a price feed would accept it.
+    Inspect(inspect::InspectArgs),
+}
+
+#[derive(Args)]
+struct FeedArg {
+    /// ID of the price feed.
+    #[arg(long, env = "PRICE_FEED_ID")]
+    feed_id: u32,
+}
+
+#[derive(Args)]
+#[group(required = true, multiple = true)]
+struct UpdateSource {
+    /// Hex-encoded update.
+    #[arg(long, env = "LAZER_UPDATE_HEX")]
+    update_hex: Option<String>,
+    /// File containing the binary update. Takes precedence over `--update-hex`.
+    #[arg(long)]
+    update_file: Option<PathBuf>,
+}
+
+impl UpdateSource {
+    fn read(&self) -> anyhow::Result<Vec<u8>> {
+        match (&self.update_hex, &self.update_file) {
+            (_, Some(path)) => {
+                fs::read(path).with_context(|| format!("failed to read {}", path.display()))
+            }
+            (Some(update_hex), None) => hex::decode(update_hex).context("invalid update hex"),
+            (None, None) => anyhow::bail!("no update given"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum DeviationModeArg {
+    Reject,
+    Quarantine,
+}
+
+impl From<DeviationModeArg> for DeviationMode {
+    fn from(value: DeviationModeArg) -> Self {
+        match value {
+            DeviationModeArg::Reject => DeviationMode::Reject,
+            DeviationModeArg::Quarantine => DeviationMode::Quarantine,
+        }
+    }
+}
+
+/// Connection to the cluster and the example program, with the payer signing transactions.
+struct Client {
+    rpc: RpcClient,
+    keypair: Keypair,
+    program_id: Pubkey,
+}
+
+fn main() -> anyhow::Result<()> {
+    env_logger::init();
+    let cli = Cli::parse();
+    let rpc_url = cli
+        .rpc_url
+        .context("--rpc-url or SOLANA_RPC_URL is required")?;
+    let keypair_path = cli
+        .keypair
+        .context("--keypair or SOLANA_KEYPAIR_FILE is required")?;
+    let program_id = cli
+        .program_id
+        .context("--program-id or EXAMPLE_PROGRAM_PUBKEY is required")?;
+    let keypair = read_keypair_file(&keypair_path)
+        .map_err(|err| anyhow::anyhow!("failed to read {}: {err}", keypair_path.display()))?;
+    let client = Client {
+        rpc: RpcClient::new(rpc_url),
+        keypair,
+        program_id,
+    };
+
+    match cli.command {
+        Command::Init {
+            feed,
+            max_age_us,
+            accepted_channel_ids,
+            max_deviation_bps_per_second,
+            deviation_mode,
+        } => {
+            let accepted_channels = accepted_channel_ids
+                .into_iter()
+                .fold(0, |channels, channel_id| {
+                    channels | channel_bit(ChannelId(channel_id))
+                });
+            let signature = client.send(&[instructions::initialize(
+                &client.program_id,
+                &client.keypair.pubkey(),
+                &InitializeArgs {
+                    price_feed_id: feed.feed_id,
+                    max_age_us,
+                    accepted_channels,
+                    max_deviation_bps_per_second,
+                    deviation_mode: DeviationMode::from(deviation_mode) as u8,
+                    authority: client.keypair.pubkey(),
+                },
+            )])?;
+            println!("OK {signature:?}");
+        }
+        Command::Update { feed, update } => {
+            let message = update.read()?;
+            // `update_auto` panics on malformed messages.
+            if message.starts_with(&SOLANA_FORMAT_MAGIC.to_le_bytes()) {
+                SolanaMessage::deserialize_slice(&message)
+                    .context("update is not a valid Solana message")?;
+            } else {
+                LeEcdsaMessage::deserialize_slice(&message)
+                    .context("update is neither a Solana nor an LE-ECDSA message")?;
+            }
+            let update_instructions = instructions::update_auto(
+                &client.program_id,
+                &client.keypair.pubkey(),
+                &client.pyth_treasury()?,
+                &message,
+                &UpdateArgs::new(UnmatchedFeedMode::Reject),
+                &[feed.feed_id],
+            );
+            client.send_update(update_instructions, feed.feed_id)?;
+        }
+        Command::UpdateEcdsa { feed, update } => {
+            let message = update.read()?;
+            LeEcdsaMessage::deserialize_slice(&message)
+                .context("update is not a valid LE-ECDSA message")?;
+            let update_instructions = instructions::update_ecdsa(
+                &client.program_id,
+                &client.keypair.pubkey(),
+                &client.pyth_treasury()?,
+                &message,
+                &UpdateArgs::new(UnmatchedFeedMode::Reject),
+                &[feed.feed_id],
+            );
+            client.send_update(update_instructions, feed.feed_id)?;
+        }
+        Command::UpdateBatch { updates_hex } => {
+            let messages = updates_hex
+                .iter()
+                .map(|message| hex::decode(message.trim()))
+                .collect::<Result<Vec<_>, _>>()
+                .context("invalid update hex")?;
+            let pyth_treasury = client.pyth_treasury()?;
+            let allowlisted_price_feed_ids =
+                client.allowlisted_price_feed_ids(&price_feed_ids(&messages)?)?;
+            let latest_blockhash = client.rpc.get_latest_blockhash()?;
+            let mut remaining = &messages[..];
+            while !remaining.is_empty() {
+                // Pack as many updates as fit into the transaction.
+                let mut packed = None;
+                for count in 1..=remaining.len() {
+                    let tx = client.update_batch_transaction(
+                        &pyth_treasury,
+                        &remaining[..count],
+                        &allowlisted_price_feed_ids,
+                        latest_blockhash,
+                    )?;
+                    if bincode::serialized_size(&tx)? > PACKET_DATA_SIZE as u64 {
+                        break;
+                    }
+                    packed = Some((count, tx));
+                }
+                let (count, tx) = packed.context("update doesn't fit into a transaction")?;
+                let signature = client.send_transaction(&tx)?;
+                println!("OK {count} updates {signature:?}");
+                client.print_price_update_events(&signature)?;
+                remaining = &remaining[count..];
+            }
+        }
+        Command::Read(args) => read::run(&client, args)?,
+        Command::SetUpdaterAllowlist { feed, updaters } => {
+            let signature = client.send(&[instructions::set_updater_allowlist(
+                &client.program_id,
+                &client.keypair.pubkey(),
+                feed.feed_id,
+                &updaters,
+                !updaters.is_empty(),
+            )])?;
+            println!("OK {signature:?}");
+        }
+        Command::Relay(args) => relay::run(&client, args)?,
+        Command::Pause { feed } => client.set_paused(feed.feed_id, true)?,
+        Command::Unpause { feed } => client.set_paused(feed.feed_id, false)?,
+        Command::Inspect(args) => inspect::run(&client, args)?,
+    }
+    Ok(())
+}
+
+impl Client {
+    /// Fetches the state of `price_feed_id`.
+    fn state(&self, price_feed_id: u32) -> anyhow::Result<State> {
+        let (data_pda_key, _) = find_data_pda(&self.program_id, price_feed_id);
+        let state_data = self
+            .rpc
+            .get_account_data(&data_pda_key)
+            .with_context(|| format!("failed to fetch the data account of feed {price_feed_id}"))?;
+        Ok(*State::load(&state_data).context("invalid data account")?)
+    }
+
+    /// Fetches the storage of the Pyth Lazer program, which contains the trusted signers.
+    fn pyth_storage(&self) -> anyhow::Result<pyth_lazer_solana_contract::Storage> {
+        let pyth_storage_data = self
+            .rpc
+            .get_account_data(&pyth_lazer_solana_contract::STORAGE_ID)
+            .context("failed to fetch the Pyth Lazer storage account")?;
+        Ok(pyth_lazer_solana_contract::Storage::try_deserialize(
+            &mut &*pyth_storage_data,
+        )?)
+    }
+
+    /// Fetches the treasury receiving the Pyth Lazer verification fees.
+    fn pyth_treasury(&self) -> anyhow::Result<Pubkey> {
+        Ok(self.pyth_storage()?.treasury)
+    }
+
+    /// Sends the instructions updating `price_feed_id`, with its updater allowlist account
+    /// added if the allowlist is enabled, and prints the stored prices.
+    fn send_update(
+        &self,
+        mut update_instructions: Vec<Instruction>,
+        price_feed_id: u32,
+    ) -> anyhow::Result<()> {
+        if self.state(price_feed_id)?.updater_allowlist_enabled != 0 {
+            update_instructions
+                .last_mut()
+                .expect("no update instruction")
+                .accounts
+                .push(instructions::updater_allowlist_account(
+                    &self.program_id,
+                    price_feed_id,
+                ));
+        }
+        let signature = self.send(&update_instructions)?;
+        println!("OK {signature:?}");
+        self.print_price_update_events(&signature)
+    }
+
+    /// Pauses or resumes updates of `price_feed_id`.
+    fn set_paused(&self, price_feed_id: u32, paused: bool) -> anyhow::Result<()> {
+        let signature = self.send(&[instructions::set_paused(
+            &self.program_id,
+            &self.keypair.pubkey(),
+            price_feed_id,
+            paused,
+        )])?;
+        println!("OK {signature:?}");
+        Ok(())
+    }
+
+    /// Sends a transaction paid and signed by the payer.
+    fn send(&self, instructions: &[Instruction]) -> anyhow::Result<Signature> {
+        let tx = Transaction::new(
+            &[&self.keypair],
+            Message::new(instructions, Some(&self.keypair.pubkey())),
+            self.rpc.get_latest_blockhash()?,
+        );
+        self.send_transaction(&tx)
+    }
+
+    /// Sends the transaction. If it fails, the reason decoded from its logs is added to the error.
+    fn send_transaction(&self, tx: &Transaction) -> anyhow::Result<Signature> {
+        self.rpc.send_and_confirm_transaction(tx).map_err(|err| {
+            match failure_logs(&err).and_then(|logs| explain_failure(&self.program_id, logs)) {
+                Some(reason) => anyhow::Error::new(err).context(reason),
+                None => err.into(),
+            }
+        })
+    }
+
+    /// Builds a transaction applying `messages` with a single `UpdateBatch` instruction.
+    /// It includes the data accounts of all price feeds contained in the updates, and the
+    /// updater allowlist accounts of those in `allowlisted_price_feed_ids`.
+    fn update_batch_transaction(
+        &self,
+        pyth_treasury: &Pubkey,
+        messages: &[Vec<u8>],
+        allowlisted_price_feed_ids: &[u32],
+        latest_blockhash: Hash,
+    ) -> anyhow::Result<Transaction> {
+        let price_feed_ids = price_feed_ids(messages)?;
+        anyhow::ensure!(!price_feed_ids.is_empty(), "update without price feeds");
+
+        let mut update_instructions = instructions::update_batch(
+            &self.program_id,
+            &self.keypair.pubkey(),
+            pyth_treasury,
+            &messages.iter().map(Vec::as_slice).collect::<Vec<_>>(),
+            &UpdateArgs::new(UnmatchedFeedMode::Reject),
+            &price_feed_ids,
+        );
+        update_instructions
+            .last_mut()
+            .expect("no update instruction")
+            .accounts
+            .extend(
+                price_feed_ids
+                    .iter()
+                    .filter(|price_feed_id| allowlisted_price_feed_ids.contains(price_feed_id))
+                    .map(|price_feed_id| {
+                        instructions::updater_allowlist_account(&self.program_id, *price_feed_id)
+                    }),
+            );
+        Ok(Transaction::new(
+            &[&self.keypair],
+            Message::new(&update_instructions, Some(&self.keypair.pubkey())),
+            latest_blockhash,
+        ))
+    }
+
+    /// Returns the price feeds among `price_feed_ids` that have their updater allowlist enabled.
+    fn allowlisted_price_feed_ids(&self, price_feed_ids: &[u32]) -> anyhow::Result<Vec<u32>> {
+        let data_pda_keys: Vec<_> = price_feed_ids
+            .iter()
+            .map(|price_feed_id| find_data_pda(&self.program_id, *price_feed_id).0)
+            .collect();
+        let accounts = self.rpc.get_multiple_accounts(&data_pda_keys)?;
+        Ok(price_feed_ids
+            .iter()
+            .zip(accounts)
+            .filter(|(_, account)| {
+                account.as_ref().map_or(false, |account| {
+                    State::load(&account.data)
+                        .map_or(false, |state| state.updater_allowlist_enabled != 0)
+                })
+            })
+            .map(|(price_feed_id, _)| *price_feed_id)
+            .collect())
+    }
+
+    /// Prints the prices stored by the transaction, as logged by the example program.
+    fn print_price_update_events(&self, signature: &Signature) -> anyhow::Result<()> {
+        let tx = self.rpc.get_transaction_with_config(
+            signature,
+            RpcTransactionConfig {
+                encoding: Some(UiTransactionEncoding::Base64),
+                commitment: Some(self.rpc.commitment()),
+                max_supported_transaction_version: Some(0),
+            },
+        )?;
+        let logs: Option<Vec<String>> = tx
+            .transaction
+            .meta
+            .context("missing transaction metadata")?
+            .log_messages
+            .into();
+        for event in parse_price_update_events(&self.program_id, &logs.unwrap_or_default()) {
+            let verification_path = VerificationPath::from_u8(event.verification_path).map_or_else(
+                || event.verification_path.to_string(),
+                |path| format!("{path:?}"),
+            );
+            println!(
+                "price feed {}: price {} exponent {} timestamp {} channel {} verified by {}",
+                { event.price_feed_id },
+                { event.price },
+                { event.exponent },
+                { event.timestamp_us },
+                event.channel_id,
+                verification_path,
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Returns the IDs of the price feeds contained in `messages`, without duplicates.
+fn price_feed_ids(messages: &[Vec<u8>]) -> anyhow::Result<Vec<u32>> {
+    let mut price_feed_ids = Vec::new();
+    for message in messages {
+        let payload =
+            PayloadData::deserialize_slice_le(&SolanaMessage::deserialize_slice(message)?.payload)?;
+        for feed in payload.feeds {
+            if !price_feed_ids.contains(&feed.feed_id.0) {
+                price_feed_ids.push(feed.feed_id.0);
+            }
+        }
+    }
+    Ok(price_feed_ids)
+}
+
+/// Returns the logs of a transaction that failed in the preflight simulation.
+fn failure_logs(err: &ClientError) -> Option<&[String]> {
+    match err.kind() {
+        ClientErrorKind::RpcError(RpcError::RpcResponseError {
+            data: RpcResponseErrorData::SendTransactionPreflightFailure(result),
+            ..
+        }) => result.logs.as_deref(),
+        _ => None,
+    }
+}
+
+/// Returns a human-readable reason for a failed transaction based on its logs.
+fn explain_failure(program_id: &Pubkey, logs: &[String]) -> Option<String> {
+    // The runtime logs `Program <id> failed: <error>` for each failed program, starting
+    // with the innermost one.
+    let (program, error) = logs
+        .iter()
+        .find_map(|line| line.strip_prefix("Program ")?.split_once(" failed: "))?;
+    if program == program_id.to_string() {
+        let reason = error
+            .strip_prefix("custom program error: 0x")
+            .and_then(|code| u32::from_str_radix(code, 16).ok())
+            .and_then(Error::from_u32)
+            .map_or_else(|| error.to_string(), |error| error.to_string());
+        Some(format!("example program failed: {reason}"))
+    } else if program == pyth_lazer_solana_contract::ID.to_string() {
+        Some(format!(
+            "Pyth Lazer program failed to verify the message: {error}"
+        ))
+    } else {
+        None
+    }
+}
@@ -0,0 +1,217 @@
+//! The `read` command: prints the state of price feeds with decimal prices.
+
+use {
+    crate::Client,
+    anyhow::Context as _,
+    bytemuck::bytes_of,
+    chrono::{DateTime, SecondsFormat, Utc},
+    clap::Args,
+    pyth_lazer_solana_example::{State, STATE_DISCRIMINATOR},
+    serde_json::{json, Value},
+    solana_account_decoder::UiAccountEncoding,
+    solana_client::{
+        rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+        rpc_filter::{Memcmp, RpcFilterType},
+    },
+    std::{
+        collections::HashMap,
+        mem::size_of,
+        thread,
+        time::{Duration, SystemTime, UNIX_EPOCH},
+    },
+};
+
+#[derive(Args)]
+pub struct ReadArgs {
+    /// ID of the price feed. All price feeds of the program are read if omitted.
+    #[arg(long, env = "PRICE_FEED_ID")]
+    feed_id: Option<u32>,
+    /// Print JSON instead of text.
+    #[arg(long)]
+    json: bool,
+    /// Keep polling the price feeds and print them whenever they change, as one JSON object
+    /// per line with `--json`. Runs until interrupted.
+    #[arg(long)]
+    watch: bool,
+    /// Interval between polls with `--watch`, in milliseconds.
+    #[arg(long, default_value_t = 1000)]
+    interval_ms: u64,
+}
+
+pub fn run(client: &Client, args: ReadArgs) -> anyhow::Result<()> {
+    if !args.watch {
+        let states = fetch(client, args.feed_id)?;
+        if args.json {
+            let value = match args.feed_id {
+                Some(_) => to_json(&states[0]),
+                None => states.iter().map(to_json).collect(),
+            };
+            println!("{}", serde_json::to_string_pretty(&value)?);
+        } else {
+            states.iter().for_each(print_text);
+        }
+        return Ok(());
+    }
+
+    let mut printed = HashMap::<u32, State>::new();
+    loop {
+        match fetch(client, args.feed_id) {
+            Ok(states) => {
+                for state in states {
+                    let is_changed = printed
+                        .get(&{ state.price_feed })
+                        .map_or(true, |printed| bytes_of(printed) != bytes_of(&state));
+                    if !is_changed {
+                        continue;
+                    }
+                    if args.json {
+                        println!("{}", to_json(&state));
+                    } else {
+                        print_text(&state);
+                    }
+                    printed.insert(state.price_feed, state);
+                }
+            }
+            // The next poll is tried again.
+            Err(err) => eprintln!("failed to read price feeds: {err:#}"),
+        }
+        thread::sleep(Duration::from_millis(args.interval_ms));
+    }
+}
+
+/// Fetches the state of `price_feed_id`, or of all price feeds of the program ordered by ID.
+fn fetch(client: &Client, price_feed_id: Option<u32>) -> anyhow::Result<Vec<State>> {
+    if let Some(price_feed_id) = price_feed_id {
+        return Ok(vec![client.state(price_feed_id)?]);
+    }
+    let accounts = client.rpc.get_program_accounts_with_config(
+        &client.program_id,
+        RpcProgramAccountsConfig {
+            filters: Some(vec![
+                RpcFilterType::DataSize(size_of::<State>() as u64),
+                RpcFilterType::Memcmp(Memcmp::new_base58_encoded(0, &STATE_DISCRIMINATOR)),
+            ]),
+            account_config: RpcAccountInfoConfig {
+                encoding: Some(UiAccountEncoding::Base64),
+                ..RpcAccountInfoConfig::default()
+            },
+            ..RpcProgramAccountsConfig::default()
+        },
+    )?;
+    let mut states = accounts
+        .iter()
+        .map(|(_, account)| State::load(&account.data).copied())
+        .collect::<Result<Vec<_>, _>>()
+        .context("invalid data account")?;
+    states.sort_by_key(|state| state.price_feed);
+    Ok(states)
+}
+
+fn print_text(state: &State) {
+    println!("price feed {}:", { state.price_feed });
+    if state.latest_timestamp == 0 {
+        println!("  never updated");
+    } else {
+        let exponent = state.exponent();
+        let price = |value: Option<i64>| {
+            value.map_or_else(|| "none".to_string(), |value| format_price(value, exponent))
+        };
+        println!("  price: {}", price(Some(state.latest_price)));
+        println!("  best bid price: {}", price(state.best_bid_price()));
+        println!("  best ask price: {}", price(state.best_ask_price()));
+        println!("  confidence: {}", price(state.confidence()));
+        if let Some(publisher_count) = state.publisher_count() {
+            println!("  publisher count: {publisher_count}");
+        }
+        println!("  timestamp: {}", format_timestamp(state.latest_timestamp));
+    }
+    if state.pending_timestamp != 0 {
+        println!(
+            "  quarantined price: {} at {}",
+            format_price(state.pending_price, state.exponent()),
+            format_timestamp(state.pending_timestamp)
+        );
+    }
+    if state.paused != 0 {
+        println!("  updates paused");
+    }
+}
+
+/// Returns the state as JSON. Prices are decimal strings so that no precision is lost,
+/// and the exponent is `null` if the prices are raw integers.
+fn to_json(state: &State) -> Value {
+    let price_feed_id = state.price_feed;
+    let is_updated = state.latest_timestamp != 0;
+    let exponent = state.exponent();
+    let price = |value: Option<i64>| {
+        value
+            .filter(|_| is_updated)
+            .map(|value| decimal(value, exponent.unwrap_or(0)))
+    };
+    let is_quarantined = state.pending_timestamp != 0;
+    json!({
+        "price_feed_id": price_feed_id,
+        "price": price(Some(state.latest_price)),
+        "best_bid_price": price(state.best_bid_price()),
+        "best_ask_price": price(state.best_ask_price()),
+        "confidence": price(state.confidence()),
+        "exponent": exponent,
+        "publisher_count": state.publisher_count(),
+        "timestamp": is_updated.then(|| rfc3339(state.latest_timestamp)),
+        "timestamp_us": is_updated.then_some(state.latest_timestamp),
+        "age_us": is_updated.then(|| age(state.latest_timestamp).as_micros() as u64),
+        "quarantined_price": is_quarantined.then(|| decimal(state.pending_price, exponent.unwrap_or(0))),
+        "quarantined_timestamp": is_quarantined.then(|| rfc3339(state.pending_timestamp)),
+        "paused": state.paused != 0,
+    })
+}
+
+fn format_price(value: i64, exponent: Option<i16>) -> String {
+    match exponent {
+        Some(exponent) => decimal(value, exponent),
+        None => format!("{value} (no exponent)"),
+    }
+}
+
+fn format_timestamp(timestamp_us: u64) -> String {
+    format!("{} ({:.3?} ago)", rfc3339(timestamp_us), age(timestamp_us))
+}
+
+/// Formats `value * 10^exponent` as a decimal number without rounding.
+fn decimal(value: i64, exponent: i16) -> String {
+    let sign = if value < 0 { "-" } else { "" };
+    let digits = value.unsigned_abs().to_string();
+    let Ok(scale) = usize::try_from(-i32::from(exponent)) else {
+        let zeros = if value == 0 {
+            0
+        } else {
+            exponent.unsigned_abs()
+        };
+        return format!("{sign}{digits}{}", "0".repeat(zeros.into()));
+    };
+    let digits = format!("{digits:0>width$}", width = scale + 1);
+    let (integer, fraction) = digits.split_at(digits.len() - scale);
+    if fraction.is_empty() {
+        format!("{sign}{integer}")
+    } else {
+        format!("{sign}{integer}.{fraction}")
+    }
+}
+
+fn rfc3339(timestamp_us: u64) -> String {
+    i64::try_from(timestamp_us)
+        .ok()
+        .and_then(DateTime::<Utc>::from_timestamp_micros)
+        .map_or_else(
+            || format!("{timestamp_us} us"),
+            |timestamp| timestamp.to_rfc3339_opts(SecondsFormat::Micros, true),
+        )
+}
+
+/// Returns the time elapsed since `timestamp_us` according to the local clock.
+fn age(timestamp_us: u64) -> Duration {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    now.saturating_sub(Duration::from_micros(timestamp_us))
+}
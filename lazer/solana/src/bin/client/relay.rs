@@ -0,0 +1,356 @@
+//! The `relay` command: streams updates from a Pyth Lazer websocket endpoint
+//! and applies them to the example program.
+
+use {
+    crate::Client,
+    anyhow::Context as _,
+    clap::{Args, ValueEnum},
+    futures_util::{SinkExt, StreamExt},
+    pyth_lazer_solana_contract::protocol::{
+        message::{LeEcdsaMessage, SolanaMessage},
+        payload::{PayloadData, PayloadPropertyValue},
+        router::{
+            Channel, DeliveryFormat, Format, JsonBinaryEncoding, PriceFeedId, PriceFeedProperty,
+            SubscriptionParams, SubscriptionParamsRepr,
+        },
+        subscription::{Request, Response, SubscribeRequest, SubscriptionId},
+    },
+    pyth_lazer_solana_example::{instructions, UnmatchedFeedMode, UpdateArgs},
+    solana_client::client_error::ClientError,
+    solana_sdk::{
+        commitment_config::CommitmentConfig,
+        instruction::Instruction,
+        signature::Signature,
+        signer::Signer,
+        transaction::{Transaction, TransactionError},
+    },
+    std::{
+        collections::HashMap,
+        sync::mpsc,
+        thread,
+        time::{Duration, Instant},
+    },
+    tokio_tungstenite::{
+        connect_async,
+        tungstenite::{client::IntoClientRequest, http::header::AUTHORIZATION, Message},
+    },
+};
+
+/// Delay before the first reconnection attempt. It doubles after each failed attempt.
+const MIN_RECONNECT_DELAY: Duration = Duration::from_secs(1);
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(30);
+
+/// The connection is considered dead if no message arrives for this long.
+const STREAM_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Args)]
+pub struct RelayArgs {
+    /// URL of the Pyth Lazer websocket endpoint.
+    #[arg(
+        long,
+        env = "LAZER_WS_URL",
+        default_value = "wss://pyth-lazer.dourolabs.app/v1/stream"
+    )]
+    url: String,
+    /// Access token sent as a bearer token.
+    #[arg(long, env = "LAZER_ACCESS_TOKEN")]
+    access_token: Option<String>,
+    /// Comma-separated IDs of the price feeds to relay. Their data accounts must exist.
+    #[arg(long, env = "PRICE_FEED_IDS", value_delimiter = ',', required = true)]
+    feed_ids: Vec<u32>,
+    /// Channel to subscribe to, e.g. `real_time` or `fixed_rate@200ms`.
+    /// The price feeds must accept it.
+    #[arg(long, default_value = "fixed_rate@200ms", value_parser = parse_channel)]
+    channel: Channel,
+    /// Format of the signed updates.
+    #[arg(long, value_enum, default_value_t = RelayFormat::Solana)]
+    format: RelayFormat,
+    /// Push an update once a price moves this many basis points from the last pushed price.
+    #[arg(long, default_value_t = 10)]
+    threshold_bps: u32,
+    /// Push an update once this many seconds passed since the last push, even if prices
+    /// didn't move.
+    #[arg(long, default_value_t = 60)]
+    heartbeat_secs: u64,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum RelayFormat {
+    /// Verified with an ed25519 instruction.
+    Solana,
+    /// Verified by the Pyth Lazer program.
+    LeEcdsa,
+}
+
+fn parse_channel(value: &str) -> Result<Channel, String> {
+    serde_json::from_value(value.into()).map_err(|err| err.to_string())
+}
+
+/// Latest price pushed for a price feed.
+struct Pushed {
+    at: Instant,
+    price: Option<i64>,
+}
+
+pub fn run(client: &Client, args: RelayArgs) -> anyhow::Result<()> {
+    let pyth_treasury = client.pyth_treasury()?;
+    let allowlisted_price_feed_ids = client.allowlisted_price_feed_ids(&args.feed_ids)?;
+    let request = subscribe_request(&args)?;
+    let heartbeat = Duration::from_secs(args.heartbeat_secs);
+
+    // The websocket is read on its own thread so that the stream keeps flowing
+    // while transactions are confirmed.
+    let (sender, receiver) = mpsc::channel();
+    let runtime = tokio::runtime::Runtime::new()?;
+    let (url, access_token) = (args.url.clone(), args.access_token.clone());
+    thread::spawn(move || {
+        runtime.block_on(stream_updates(
+            &url,
+            access_token.as_deref(),
+            &request,
+            &sender,
+        ))
+    });
+
+    let mut pushed = HashMap::<u32, Pushed>::new();
+    loop {
+        let mut message = receiver.recv().context("update stream stopped")?;
+        // Updates that arrived while the previous transaction was confirmed are outdated.
+        while let Ok(next) = receiver.try_recv() {
+            message = next;
+        }
+        let payload = match decode_payload(&message) {
+            Ok(payload) => payload,
+            Err(err) => {
+                eprintln!("skipping invalid update: {err:#}");
+                continue;
+            }
+        };
+        if !should_push(&pushed, &payload, args.threshold_bps, heartbeat) {
+            continue;
+        }
+
+        let price_feed_ids: Vec<u32> = payload.feeds.iter().map(|feed| feed.feed_id.0).collect();
+        let mut update_instructions = instructions::update_auto(
+            &client.program_id,
+            &client.keypair.pubkey(),
+            &pyth_treasury,
+            &message,
+            &UpdateArgs::new(UnmatchedFeedMode::Reject),
+            &price_feed_ids,
+        );
+        update_instructions
+            .last_mut()
+            .expect("no update instruction")
+            .accounts
+            .extend(
+                price_feed_ids
+                    .iter()
+                    .filter(|price_feed_id| allowlisted_price_feed_ids.contains(price_feed_id))
+                    .map(|price_feed_id| {
+                        instructions::updater_allowlist_account(&client.program_id, *price_feed_id)
+                    }),
+            );
+        match send_update(client, &update_instructions) {
+            Ok(signature) => {
+                println!(
+                    "pushed update with timestamp {} {signature:?}",
+                    payload.timestamp_us.0
+                );
+                let now = Instant::now();
+                for feed in &payload.feeds {
+                    pushed.insert(
+                        feed.feed_id.0,
+                        Pushed {
+                            at: now,
+                            price: feed_price(&feed.properties),
+                        },
+                    );
+                }
+            }
+            // The next update is tried again.
+            Err(err) => eprintln!("failed to push update: {err:#}"),
+        }
+    }
+}
+
+fn subscribe_request(args: &RelayArgs) -> anyhow::Result<Request> {
+    let params = SubscriptionParams::new(SubscriptionParamsRepr {
+        price_feed_ids: args.feed_ids.iter().copied().map(PriceFeedId).collect(),
+        properties: vec![
+            PriceFeedProperty::Price,
+            PriceFeedProperty::BestBidPrice,
+            PriceFeedProperty::BestAskPrice,
+            PriceFeedProperty::PublisherCount,
+            PriceFeedProperty::Exponent,
+            PriceFeedProperty::Confidence,
+        ],
+        formats: vec![match args.format {
+            RelayFormat::Solana => Format::Solana,
+            RelayFormat::LeEcdsa => Format::LeEcdsa,
+        }],
+        delivery_format: DeliveryFormat::Json,
+        json_binary_encoding: JsonBinaryEncoding::Hex,
+        parsed: false,
+        channel: args.channel,
+        ignore_invalid_feed_ids: false,
+    })
+    .map_err(anyhow::Error::msg)?;
+    Ok(Request::Subscribe(SubscribeRequest {
+        subscription_id: SubscriptionId(1),
+        params,
+    }))
+}
+
+/// Forwards the signed updates of the subscription to `sender`, reconnecting whenever
+/// the connection fails. Returns once the receiver is dropped.
+async fn stream_updates(
+    url: &str,
+    access_token: Option<&str>,
+    request: &Request,
+    sender: &mpsc::Sender<Vec<u8>>,
+) {
+    let mut reconnect_delay = MIN_RECONNECT_DELAY;
+    loop {
+        let connected_at = Instant::now();
+        match stream_session(url, access_token, request, sender).await {
+            Ok(()) => return,
+            Err(err) => eprintln!("update stream failed: {err:#}"),
+        }
+        // Start over with a short delay if the connection worked for a while.
+        if connected_at.elapsed() > MAX_RECONNECT_DELAY {
+            reconnect_delay = MIN_RECONNECT_DELAY;
+        }
+        eprintln!("reconnecting in {reconnect_delay:?}");
+        tokio::time::sleep(reconnect_delay).await;
+        reconnect_delay = (reconnect_delay * 2).min(MAX_RECONNECT_DELAY);
+    }
+}
+
+/// Streams updates over a single connection. Returns `Ok` once the receiver is dropped.
+async fn stream_session(
+    url: &str,
+    access_token: Option<&str>,
+    request: &Request,
+    sender: &mpsc::Sender<Vec<u8>>,
+) -> anyhow::Result<()> {
+    let mut http_request = url.into_client_request()?;
+    if let Some(access_token) = access_token {
+        http_request
+            .headers_mut()
+            .insert(AUTHORIZATION, format!("Bearer {access_token}").parse()?);
+    }
+    let (mut stream, _) = connect_async(http_request)
+        .await
+        .with_context(|| format!("failed to connect to {url}"))?;
+    stream
+        .send(Message::Text(serde_json::to_string(request)?))
+        .await?;
+
+    loop {
+        let message = tokio::time::timeout(STREAM_TIMEOUT, stream.next())
+            .await
+            .context("no messages received in time")?
+            .context("connection closed")??;
+        let text = match message {
+            Message::Text(text) => text,
+            Message::Close(frame) => anyhow::bail!("connection closed by the server: {frame:?}"),
+            // Pings are answered by the stream itself.
+            _ => continue,
+        };
+        match serde_json::from_str::<Response>(&text)? {
+            Response::StreamUpdated(update) => {
+                let data = update
+                    .payload
+                    .solana
+                    .or(update.payload.le_ecdsa)
+                    .context("update without a signed message")?;
+                if sender.send(hex::decode(data.data)?).is_err() {
+                    return Ok(());
+                }
+            }
+            Response::Subscribed(_) => println!("subscribed to {url}"),
+            Response::SubscribedWithInvalidFeedIdsIgnored(response) => {
+                anyhow::bail!(
+                    "invalid price feeds: {:?}",
+                    response.ignored_invalid_feed_ids
+                )
+            }
+            Response::Error(response) => anyhow::bail!("server error: {}", response.error),
+            Response::SubscriptionError(response) => {
+                anyhow::bail!("subscription failed: {}", response.error)
+            }
+            Response::Unsubscribed(_) => anyhow::bail!("unsubscribed by the server"),
+        }
+    }
+}
+
+/// Decodes the payload of a signed update in either format, without verifying it.
+fn decode_payload(message: &[u8]) -> anyhow::Result<PayloadData> {
+    let payload = match SolanaMessage::deserialize_slice(message) {
+        Ok(message) => message.payload,
+        Err(_) => LeEcdsaMessage::deserialize_slice(message)?.payload,
+    };
+    PayloadData::deserialize_slice_le(&payload)
+}
+
+/// Returns whether the update has to be pushed: a price feed wasn't pushed yet or longer
+/// than `heartbeat` ago, or its price moved by at least `threshold_bps` since then.
+fn should_push(
+    pushed: &HashMap<u32, Pushed>,
+    payload: &PayloadData,
+    threshold_bps: u32,
+    heartbeat: Duration,
+) -> bool {
+    payload.feeds.iter().any(|feed| {
+        let Some(last) = pushed.get(&feed.feed_id.0) else {
+            return true;
+        };
+        if last.at.elapsed() >= heartbeat {
+            return true;
+        }
+        match (last.price, feed_price(&feed.properties)) {
+            (Some(last_price), Some(price)) => {
+                let change = (i128::from(price) - i128::from(last_price)).unsigned_abs();
+                change * 10_000 >= u128::from(threshold_bps) * last_price.unsigned_abs() as u128
+            }
+            (last_price, price) => last_price != price,
+        }
+    })
+}
+
+pub fn feed_price(properties: &[PayloadPropertyValue]) -> Option<i64> {
+    properties.iter().find_map(|property| match property {
+        PayloadPropertyValue::Price(price) => price.map(|price| price.into_inner().get()),
+        _ => None,
+    })
+}
+
+/// Sends `instructions` in a transaction. If it fails because its blockhash expired before
+/// it was confirmed, it's sent once more with the latest blockhash.
+fn send_update(client: &Client, instructions: &[Instruction]) -> anyhow::Result<Signature> {
+    let blockhash = client.rpc.get_latest_blockhash()?;
+    let tx = Transaction::new_signed_with_payer(
+        instructions,
+        Some(&client.keypair.pubkey()),
+        &[&client.keypair],
+        blockhash,
+    );
+    let err = match client.send_transaction(&tx) {
+        Ok(signature) => return Ok(signature),
+        Err(err) => err,
+    };
+    let is_blockhash_not_found = err.downcast_ref::<ClientError>().map_or(false, |err| {
+        err.get_transaction_error() == Some(TransactionError::BlockhashNotFound)
+    });
+    // The confirmation times out once the blockhash is no longer valid.
+    let is_expired = is_blockhash_not_found
+        || !client
+            .rpc
+            .is_blockhash_valid(&blockhash, CommitmentConfig::processed())?;
+    if is_expired {
+        client.send(instructions)
+    } else {
+        Err(err)
+    }
+}
@@ -0,0 +1,74 @@
+//! Helpers for other programs reading prices of the example program via CPI.
+
+use {
+    crate::{find_data_pda, Error, GetPriceArgs, Instruction as ExampleInstruction, PriceResult},
+    bytemuck::{bytes_of, bytes_of_mut, Zeroable},
+    solana_program::{
+        account_info::AccountInfo,
+        instruction::{AccountMeta, Instruction},
+        program::{get_return_data, invoke},
+        program_error::ProgramError,
+        pubkey::Pubkey,
+    },
+    std::mem::size_of,
+};
+
+/// Returns a `GetPrice` instruction reading the price of `price_feed_id`.
+pub fn get_price_instruction(
+    program_id: &Pubkey,
+    price_feed_id: u32,
+    max_age_us: u64,
+) -> Instruction {
+    get_price_instruction_for_account(
+        program_id,
+        &find_data_pda(program_id, price_feed_id).0,
+        max_age_us,
+    )
+}
+
+fn get_price_instruction_for_account(
+    program_id: &Pubkey,
+    data_account: &Pubkey,
+    max_age_us: u64,
+) -> Instruction {
+    let mut data = vec![ExampleInstruction::GetPrice as u8];
+    data.extend_from_slice(bytes_of(&GetPriceArgs { max_age_us }));
+    Instruction::new_with_bytes(
+        *program_id,
+        &data,
+        vec![AccountMeta::new_readonly(*data_account, false)],
+    )
+}
+
+/// Reads the price stored in `data_account` by invoking `GetPrice` of `example_program`.
+/// Fails if the price is older than `max_age_us`. The price of a paused price feed is
+/// returned with `PriceResult::paused` set, the caller decides whether to use it.
+pub fn get_price<'info>(
+    example_program: &AccountInfo<'info>,
+    data_account: &AccountInfo<'info>,
+    max_age_us: u64,
+) -> Result<PriceResult, ProgramError> {
+    invoke(
+        &get_price_instruction_for_account(example_program.key, data_account.key, max_age_us),
+        &[data_account.clone(), example_program.clone()],
+    )?;
+    let (program_id, return_data) = get_return_data().ok_or(ProgramError::InvalidAccountData)?;
+    if program_id != *example_program.key {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    decode_price(&return_data)
+}
+
+/// Decodes the return data of `GetPrice` of any version. Fields added after the version of
+/// the return data are zero, e.g. a version 1 result is never paused, and fields added in
+/// versions newer than `PRICE_RESULT_VERSION` are ignored.
+pub fn decode_price(return_data: &[u8]) -> Result<PriceResult, ProgramError> {
+    if return_data.first().map_or(true, |&version| version == 0) {
+        return Err(Error::UnsupportedVersion.into());
+    }
+    // Trailing zero bytes are trimmed from the return data reported for transactions.
+    let len = return_data.len().min(size_of::<PriceResult>());
+    let mut result = PriceResult::zeroed();
+    bytes_of_mut(&mut result)[..len].copy_from_slice(&return_data[..len]);
+    Ok(result)
+}
@@ -0,0 +1,83 @@
+use {
+    num_derive::FromPrimitive,
+    solana_program::program_error::ProgramError,
+    std::fmt::{self, Display},
+};
+
+/// Errors specific to the example program. They are returned as `ProgramError::Custom`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, FromPrimitive)]
+#[repr(u32)]
+pub enum Error {
+    /// The update is older than the maximum age configured for the price feed
+    /// or than the constraints of `UpdateArgs` allow.
+    PriceStale = 1,
+    /// The update timestamp is ahead of the cluster clock by more than `MAX_FUTURE_SKEW_US`.
+    TimestampInFuture = 2,
+    /// The signer is not the authority of the price feed.
+    Unauthorized = 3,
+    /// The data account was closed earlier in the same transaction.
+    AccountClosed = 4,
+    /// The history account doesn't contain a price from the start of the requested window.
+    InsufficientHistory = 5,
+    /// The prices in the requested window don't have the same exponent.
+    ExponentMismatch = 6,
+    /// The price moved further from the previous price than the configured maximum deviation.
+    DeviationExceeded = 7,
+    /// The price feeds of the update don't match the data accounts passed to the instruction.
+    WrongPriceFeed = 8,
+    /// The update comes from a channel that the price feed doesn't accept.
+    WrongChannel = 9,
+    /// The update is not newer than the latest (or quarantined) update of the price feed.
+    NonMonotonicTimestamp = 10,
+    /// The update doesn't contain the price of the price feed,
+    /// or the price feed hasn't received a price yet.
+    MissingPrice = 11,
+    /// An account is not the PDA of the price feed that it belongs to.
+    InvalidPda = 12,
+    /// The Pyth Lazer message or its payload can't be parsed.
+    InvalidMessage = 13,
+    /// The Pyth Lazer program didn't verify the message. Note that on-chain, a failure inside
+    /// the Pyth Lazer program usually aborts the transaction with that program's own error.
+    VerificationFailed = 14,
+    /// The instruction arguments have a layout version that the program doesn't support.
+    UnsupportedVersion = 15,
+    /// No ed25519 instruction preceding the update checks the signature of its message.
+    SignatureNotFound = 16,
+    /// The updater allowlist of the price feed is enabled and the payer of the update
+    /// is not one of the allowed updaters, or the allowlist account wasn't passed.
+    UnauthorizedUpdater = 17,
+    /// Updates of the price feed are paused by its authority.
+    Paused = 18,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let description = match self {
+            Error::PriceStale => "price update is too old",
+            Error::TimestampInFuture => "price update timestamp is in the future",
+            Error::Unauthorized => "signer is not the authority of the price feed",
+            Error::AccountClosed => "data account is closed",
+            Error::InsufficientHistory => "not enough price history for the requested window",
+            Error::ExponentMismatch => "prices in the requested window have different exponents",
+            Error::DeviationExceeded => "price moved more than the maximum deviation",
+            Error::WrongPriceFeed => "price feeds don't match the data accounts",
+            Error::WrongChannel => "price update comes from a channel that is not accepted",
+            Error::NonMonotonicTimestamp => "price update is not newer than the stored one",
+            Error::MissingPrice => "price is missing",
+            Error::InvalidPda => "account is not the expected PDA",
+            Error::InvalidMessage => "invalid Pyth Lazer message",
+            Error::VerificationFailed => "Pyth Lazer message verification failed",
+            Error::UnsupportedVersion => "unsupported instruction arguments version",
+            Error::SignatureNotFound => "no ed25519 instruction checks the message signature",
+            Error::UnauthorizedUpdater => "payer is not an allowed updater of the price feed",
+            Error::Paused => "price feed updates are paused",
+        };
+        f.write_str(description)
+    }
+}
+
+impl From<Error> for ProgramError {
+    fn from(value: Error) -> Self {
+        ProgramError::Custom(value as u32)
+    }
+}
@@ -0,0 +1,49 @@
+//! Parsing of the events logged by the example program.
+
+use {
+    crate::{PriceUpdateEvent, PRICE_UPDATE_EVENT_DISCRIMINATOR},
+    base64::{engine::general_purpose::STANDARD, Engine},
+    bytemuck::try_pod_read_unaligned,
+    solana_program::pubkey::Pubkey,
+};
+
+/// Returns the `PriceUpdateEvent`s logged by `program_id` in the logs of a transaction.
+/// Data logged by other programs, including programs invoked by `program_id`, is ignored.
+pub fn parse_price_update_events(program_id: &Pubkey, logs: &[String]) -> Vec<PriceUpdateEvent> {
+    let program_id = program_id.to_string();
+    // Programs that are currently executing, the innermost one is last.
+    let mut programs = Vec::new();
+    let mut events = Vec::new();
+    for line in logs {
+        let Some(line) = line.strip_prefix("Program ") else {
+            continue;
+        };
+        if let Some(data) = line.strip_prefix("data: ") {
+            if programs.last() == Some(&program_id.as_str()) {
+                events.extend(parse_price_update_event(data));
+            }
+        } else if let Some((program, rest)) = line.split_once(' ') {
+            if rest.starts_with("invoke [") {
+                programs.push(program);
+            } else if rest == "success" || rest.starts_with("failed: ") {
+                programs.pop();
+            }
+        }
+    }
+    events
+}
+
+/// Parses the base64 fields of a `Program data:` log line.
+fn parse_price_update_event(data: &str) -> Option<PriceUpdateEvent> {
+    let fields = data
+        .split(' ')
+        .map(|field| STANDARD.decode(field))
+        .collect::<Result<Vec<_>, _>>()
+        .ok()?;
+    match fields.as_slice() {
+        [discriminator, event] if *discriminator == PRICE_UPDATE_EVENT_DISCRIMINATOR => {
+            try_pod_read_unaligned(event).ok()
+        }
+        _ => None,
+    }
+}
@@ -0,0 +1,396 @@
+//! Builders of the example program instructions for off-chain clients.
+//!
+//! The update builders return all instructions that have to be added to the transaction,
+//! including the ed25519 instruction verifying the signatures of the updates.
+
+use {
+    crate::{
+        cpi, find_data_pda, find_history_pda, find_updater_allowlist_pda, GetTwapArgs,
+        InitializeArgs, Instruction as ExampleInstruction, ProposeAuthorityArgs, SetConfigArgs,
+        SetPausedArgs, SetUpdaterAllowlistArgs, UpdateArgs, DISCOVER_ED25519_INSTRUCTION,
+        MAX_ALLOWED_UPDATERS,
+    },
+    bytemuck::bytes_of,
+    pyth_lazer_solana_contract::{
+        ed25519_program_args, protocol::message::format_magics_le::SOLANA_FORMAT_MAGIC,
+        Ed25519SignatureOffsets,
+    },
+    solana_program::{
+        ed25519_program,
+        instruction::{AccountMeta, Instruction},
+        pubkey::Pubkey,
+        system_program, sysvar,
+    },
+};
+
+/// Returns the `Initialize` instruction creating the data account of `args.price_feed_id`.
+/// `args.authority` must sign the transaction.
+pub fn initialize(program_id: &Pubkey, payer: &Pubkey, args: &InitializeArgs) -> Instruction {
+    Instruction::new_with_bytes(
+        *program_id,
+        &instruction_data(ExampleInstruction::Initialize, bytes_of(args)),
+        vec![
+            AccountMeta::new(*payer, true),
+            AccountMeta::new(find_data_pda(program_id, args.price_feed_id).0, false),
+            AccountMeta::new_readonly(system_program::ID, false),
+            AccountMeta::new_readonly(args.authority, true),
+        ],
+    )
+}
+
+/// Returns the ed25519 instruction and the `Update` instruction applying `message`
+/// to the data accounts of `price_feed_ids`.
+///
+/// The instructions must be placed at `update_args.ed25519_instruction_index` within
+/// the transaction, or at its start if the index is `DISCOVER_ED25519_INSTRUCTION`.
+/// The ed25519 instruction only contains the signature of `message`, so
+/// `update_args.signature_index` should be 0.
+///
+/// Panics if `message` is too short or `price_feed_ids` is empty.
+pub fn update(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    pyth_treasury: &Pubkey,
+    message: &[u8],
+    update_args: &UpdateArgs,
+    price_feed_ids: &[u32],
+) -> Vec<Instruction> {
+    let instruction_index = update_instruction_index(update_args);
+    let mut data = instruction_data(ExampleInstruction::Update, bytes_of(update_args));
+    let signature = signature_offsets(message, instruction_index, data.len());
+    data.extend_from_slice(message);
+    vec![
+        ed25519_instruction(&[signature]),
+        Instruction::new_with_bytes(
+            *program_id,
+            &data,
+            update_accounts(program_id, payer, pyth_treasury, price_feed_ids),
+        ),
+    ]
+}
+
+/// Returns the ed25519 instruction and the `UpdateBatch` instruction applying `messages`
+/// to the data accounts of `price_feed_ids`. See `update` for where to place them.
+///
+/// Panics if a message is too short or too long, or `price_feed_ids` is empty.
+pub fn update_batch(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    pyth_treasury: &Pubkey,
+    messages: &[&[u8]],
+    update_args: &UpdateArgs,
+    price_feed_ids: &[u32],
+) -> Vec<Instruction> {
+    let instruction_index = update_instruction_index(update_args);
+    let mut data = instruction_data(ExampleInstruction::UpdateBatch, bytes_of(update_args));
+    let mut signatures = Vec::new();
+    for message in messages {
+        let len = u16::try_from(message.len()).expect("message is too long");
+        data.extend_from_slice(&len.to_le_bytes());
+        signatures.push(signature_offsets(message, instruction_index, data.len()));
+        data.extend_from_slice(message);
+    }
+    vec![
+        ed25519_instruction(&signatures),
+        Instruction::new_with_bytes(
+            *program_id,
+            &data,
+            update_accounts(program_id, payer, pyth_treasury, price_feed_ids),
+        ),
+    ]
+}
+
+/// Returns the `UpdateEcdsa` instruction applying `message` to the data accounts
+/// of `price_feed_ids`. The signature is verified by the Pyth Lazer program itself,
+/// so no other instructions are needed.
+///
+/// Panics if `price_feed_ids` is empty.
+pub fn update_ecdsa(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    pyth_treasury: &Pubkey,
+    message: &[u8],
+    update_args: &UpdateArgs,
+    price_feed_ids: &[u32],
+) -> Vec<Instruction> {
+    let mut data = instruction_data(ExampleInstruction::UpdateEcdsa, bytes_of(update_args));
+    data.extend_from_slice(message);
+    let mut accounts = update_accounts(program_id, payer, pyth_treasury, price_feed_ids);
+    // `UpdateEcdsa` doesn't take the instructions sysvar.
+    accounts.remove(6);
+    vec![Instruction::new_with_bytes(*program_id, &data, accounts)]
+}
+
+/// Returns the `UpdateAuto` instruction applying `message` in the `SolanaMessage`
+/// or `LeEcdsaMessage` format to the data accounts of `price_feed_ids`. For a `SolanaMessage`
+/// the ed25519 instruction precedes it, see `update` for where to place them.
+///
+/// Panics if `message` is too short or `price_feed_ids` is empty.
+pub fn update_auto(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    pyth_treasury: &Pubkey,
+    message: &[u8],
+    update_args: &UpdateArgs,
+    price_feed_ids: &[u32],
+) -> Vec<Instruction> {
+    let mut data = instruction_data(ExampleInstruction::UpdateAuto, bytes_of(update_args));
+    let mut instructions = Vec::new();
+    if message.starts_with(&SOLANA_FORMAT_MAGIC.to_le_bytes()) {
+        let instruction_index = update_instruction_index(update_args);
+        let signature = signature_offsets(message, instruction_index, data.len());
+        instructions.push(ed25519_instruction(&[signature]));
+    }
+    data.extend_from_slice(message);
+    instructions.push(Instruction::new_with_bytes(
+        *program_id,
+        &data,
+        update_accounts(program_id, payer, pyth_treasury, price_feed_ids),
+    ));
+    instructions
+}
+
+/// Returns the `SetConfig` instruction updating the config of `args.price_feed_id`.
+pub fn set_config(program_id: &Pubkey, authority: &Pubkey, args: &SetConfigArgs) -> Instruction {
+    authority_instruction(
+        program_id,
+        ExampleInstruction::SetConfig,
+        bytes_of(args),
+        authority,
+        args.price_feed_id,
+    )
+}
+
+/// Returns the `ProposeAuthority` instruction proposing `new_authority` for `price_feed_id`.
+pub fn propose_authority(
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    price_feed_id: u32,
+    new_authority: &Pubkey,
+) -> Instruction {
+    authority_instruction(
+        program_id,
+        ExampleInstruction::ProposeAuthority,
+        bytes_of(&ProposeAuthorityArgs {
+            new_authority: *new_authority,
+        }),
+        authority,
+        price_feed_id,
+    )
+}
+
+/// Returns the `AcceptAuthority` instruction signed by the proposed authority.
+pub fn accept_authority(
+    program_id: &Pubkey,
+    new_authority: &Pubkey,
+    price_feed_id: u32,
+) -> Instruction {
+    authority_instruction(
+        program_id,
+        ExampleInstruction::AcceptAuthority,
+        &[],
+        new_authority,
+        price_feed_id,
+    )
+}
+
+/// Returns the `Close` instruction moving the lamports of the data account, the history account
+/// and the updater allowlist account of `price_feed_id` to `destination`.
+pub fn close(
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    price_feed_id: u32,
+    destination: &Pubkey,
+) -> Instruction {
+    let mut instruction = authority_instruction(
+        program_id,
+        ExampleInstruction::Close,
+        &[],
+        authority,
+        price_feed_id,
+    );
+    instruction.accounts.extend([
+        AccountMeta::new(*destination, false),
+        AccountMeta::new(find_history_pda(program_id, price_feed_id).0, false),
+        AccountMeta::new(
+            find_updater_allowlist_pda(program_id, price_feed_id).0,
+            false,
+        ),
+    ]);
+    instruction
+}
+
+/// Returns the `SetPaused` instruction pausing or resuming updates of `price_feed_id`.
+pub fn set_paused(
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    price_feed_id: u32,
+    paused: bool,
+) -> Instruction {
+    authority_instruction(
+        program_id,
+        ExampleInstruction::SetPaused,
+        bytes_of(&SetPausedArgs {
+            paused: paused.into(),
+        }),
+        authority,
+        price_feed_id,
+    )
+}
+
+/// Returns the `InitializeHistory` instruction creating the history account of `price_feed_id`.
+pub fn initialize_history(program_id: &Pubkey, payer: &Pubkey, price_feed_id: u32) -> Instruction {
+    Instruction::new_with_bytes(
+        *program_id,
+        &instruction_data(ExampleInstruction::InitializeHistory, &[]),
+        vec![
+            AccountMeta::new(*payer, true),
+            AccountMeta::new_readonly(find_data_pda(program_id, price_feed_id).0, false),
+            AccountMeta::new(find_history_pda(program_id, price_feed_id).0, false),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+    )
+}
+
+/// Returns the `GetTwap` instruction for the last `window_us` microseconds of `price_feed_id`.
+pub fn get_twap(program_id: &Pubkey, price_feed_id: u32, window_us: u64) -> Instruction {
+    Instruction::new_with_bytes(
+        *program_id,
+        &instruction_data(
+            ExampleInstruction::GetTwap,
+            bytes_of(&GetTwapArgs { window_us }),
+        ),
+        vec![AccountMeta::new_readonly(
+            find_history_pda(program_id, price_feed_id).0,
+            false,
+        )],
+    )
+}
+
+/// Returns the `GetPrice` instruction reading the price of `price_feed_id`.
+pub fn get_price(program_id: &Pubkey, price_feed_id: u32, max_age_us: u64) -> Instruction {
+    cpi::get_price_instruction(program_id, price_feed_id, max_age_us)
+}
+
+/// Returns the `SetUpdaterAllowlist` instruction replacing the updater allowlist
+/// of `price_feed_id` with `updaters`.
+///
+/// Panics if there are more than `MAX_ALLOWED_UPDATERS` updaters.
+pub fn set_updater_allowlist(
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    price_feed_id: u32,
+    updaters: &[Pubkey],
+    enabled: bool,
+) -> Instruction {
+    assert!(updaters.len() <= MAX_ALLOWED_UPDATERS, "too many updaters");
+    let mut args = SetUpdaterAllowlistArgs {
+        enabled: enabled.into(),
+        len: updaters.len() as u8,
+        updaters: [Pubkey::default(); MAX_ALLOWED_UPDATERS],
+    };
+    args.updaters[..updaters.len()].copy_from_slice(updaters);
+    Instruction::new_with_bytes(
+        *program_id,
+        &instruction_data(ExampleInstruction::SetUpdaterAllowlist, bytes_of(&args)),
+        vec![
+            AccountMeta::new(*authority, true),
+            AccountMeta::new(find_data_pda(program_id, price_feed_id).0, false),
+            AccountMeta::new(
+                find_updater_allowlist_pda(program_id, price_feed_id).0,
+                false,
+            ),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+    )
+}
+
+/// Returns the updater allowlist account of `price_feed_id`. It has to be appended
+/// to the accounts of an update instruction if the allowlist of the price feed is enabled.
+pub fn updater_allowlist_account(program_id: &Pubkey, price_feed_id: u32) -> AccountMeta {
+    AccountMeta::new_readonly(
+        find_updater_allowlist_pda(program_id, price_feed_id).0,
+        false,
+    )
+}
+
+fn instruction_data(instruction: ExampleInstruction, args: &[u8]) -> Vec<u8> {
+    let mut data = vec![instruction as u8];
+    data.extend_from_slice(args);
+    data
+}
+
+/// Returns an instruction that takes the authority account and the data account
+/// of `price_feed_id`.
+fn authority_instruction(
+    program_id: &Pubkey,
+    instruction: ExampleInstruction,
+    args: &[u8],
+    authority: &Pubkey,
+    price_feed_id: u32,
+) -> Instruction {
+    Instruction::new_with_bytes(
+        *program_id,
+        &instruction_data(instruction, args),
+        vec![
+            AccountMeta::new_readonly(*authority, true),
+            AccountMeta::new(find_data_pda(program_id, price_feed_id).0, false),
+        ],
+    )
+}
+
+/// Accounts of the `Update` and `UpdateBatch` instructions.
+fn update_accounts(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    pyth_treasury: &Pubkey,
+    price_feed_ids: &[u32],
+) -> Vec<AccountMeta> {
+    let (first_price_feed_id, other_price_feed_ids) =
+        price_feed_ids.split_first().expect("no price feeds");
+    let mut accounts = vec![
+        AccountMeta::new(*payer, true),
+        AccountMeta::new(find_data_pda(program_id, *first_price_feed_id).0, false),
+        AccountMeta::new(pyth_lazer_solana_contract::ID, false),
+        AccountMeta::new_readonly(pyth_lazer_solana_contract::STORAGE_ID, false),
+        AccountMeta::new(*pyth_treasury, false),
+        AccountMeta::new_readonly(system_program::ID, false),
+        AccountMeta::new_readonly(sysvar::instructions::ID, false),
+    ];
+    accounts.extend(
+        other_price_feed_ids.iter().map(|price_feed_id| {
+            AccountMeta::new(find_data_pda(program_id, *price_feed_id).0, false)
+        }),
+    );
+    accounts
+}
+
+/// Index of the update instruction within the transaction, right after the ed25519 instruction.
+fn update_instruction_index(update_args: &UpdateArgs) -> u16 {
+    match update_args.ed25519_instruction_index {
+        DISCOVER_ED25519_INSTRUCTION => 1,
+        index => index + 1,
+    }
+}
+
+/// Returns the offsets of the signature of `message` that starts at `message_offset`
+/// within the data of the update instruction.
+fn signature_offsets(
+    message: &[u8],
+    instruction_index: u16,
+    message_offset: usize,
+) -> Ed25519SignatureOffsets {
+    Ed25519SignatureOffsets::new(
+        message,
+        instruction_index,
+        message_offset.try_into().expect("update is too long"),
+    )
+}
+
+fn ed25519_instruction(signatures: &[Ed25519SignatureOffsets]) -> Instruction {
+    Instruction::new_with_bytes(
+        ed25519_program::ID,
+        &ed25519_program_args(signatures),
+        vec![],
+    )
+}
@@ -1,33 +1,48 @@
 #![allow(unexpected_cfgs)]
 
 use {
-    anchor_lang::{prelude::AccountMeta, InstructionData},
-    bytemuck::{bytes_of, try_from_bytes, try_from_bytes_mut, Pod, Zeroable},
+    anchor_lang::{prelude::AccountMeta, Discriminator},
+    bytemuck::{bytes_of, try_cast_slice, try_from_bytes, try_from_bytes_mut, Pod, Zeroable},
     num_derive::FromPrimitive,
     num_traits::FromPrimitive,
     pyth_lazer_solana_contract::{
         instruction::{VerifyEcdsaMessage, VerifyMessage},
         protocol::{
-            message::{LeEcdsaMessage, SolanaMessage},
-            payload::{PayloadData, PayloadPropertyValue},
-            router::{Channel, FixedRate},
+            message::format_magics_le::{LE_ECDSA_FORMAT_MAGIC, SOLANA_FORMAT_MAGIC},
+            payload::{PayloadData, PayloadFeedData, PayloadPropertyValue},
+            router::ChannelId,
         },
+        Ed25519SignatureOffsets,
     },
     solana_program::{
         account_info::AccountInfo,
-        declare_id,
+        clock::Clock,
+        declare_id, ed25519_program,
         entrypoint::ProgramResult,
         instruction::Instruction as ProgramInstruction,
-        program::{invoke, invoke_signed},
+        log::sol_log_data,
+        program::{invoke, invoke_signed, set_return_data},
         program_error::ProgramError,
         pubkey::Pubkey,
         rent::Rent,
         system_instruction::create_account,
-        sysvar::Sysvar,
+        sysvar::{
+            instructions::{load_current_index_checked, load_instruction_at_checked},
+            Sysvar,
+        },
     },
-    std::mem::size_of,
+    std::{iter, mem::size_of},
 };
 
+pub mod cpi;
+mod error;
+#[cfg(all(feature = "events", not(target_os = "solana")))]
+pub mod events;
+#[cfg(all(feature = "instructions", not(target_os = "solana")))]
+pub mod instructions;
+
+pub use error::Error;
+
 // rustfmt's unstable import merging feature breaks complilation.
 #[rustfmt::skip]
 use solana_program::entrypoint;
@@ -39,34 +54,129 @@ entrypoint!(process_instruction);
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, FromPrimitive)]
 pub enum Instruction {
-    /// Initialize the data PDA.
+    /// Initialize the data PDA of a price feed.
     /// Data: `InitializeArgs`
     /// Accounts:
     /// 1. payer account [writable]
-    /// 2. example data account [writable, non-existing]
+    /// 2. example data account of the price feed [writable, non-existing]
     /// 3. system program [readonly]
+    /// 4. authority account [signer]
     Initialize = 0,
-    /// Update price using a native Solana signature.
+    /// Update prices using a native Solana signature.
     /// Data: `UpdateArgs` followed by a signed Pyth Lazer update.
     /// Accounts:
     /// 1. payer account
-    /// 2. example data account [writable]
+    /// 2. example data account of an updated price feed [writable]
     /// 3. pyth program account [readonly]
     /// 4. pyth storage account [readonly]
     /// 5. pyth treasury account [writable]
     /// 6. system program [readonly]
     /// 7. instructions sysvar sysvar account [readonly]
+    /// 8. (optional, repeated) example data accounts of other updated price feeds
+    ///    and history accounts of updated price feeds [writable]
+    ///    and updater allowlist accounts of updated price feeds [readonly]
+    ///
+    /// If the updater allowlist of an updated price feed is enabled, its account must
+    /// be passed and the payer must be one of the allowed updaters.
+    /// Fails if an updated price feed is paused.
     Update = 1,
-    /// Update price using an ECDSA signature.
+    /// Update prices using an ECDSA signature.
     /// Data: `UpdateArgs` followed by a signed Pyth Lazer update.
     /// Accounts:
     /// 1. payer account
-    /// 2. example data account [writable]
+    /// 2. example data account of an updated price feed [writable]
     /// 3. pyth program account [readonly]
     /// 4. pyth storage account [readonly]
     /// 5. pyth treasury account [writable]
     /// 6. system program [readonly]
+    /// 7. (optional, repeated) example data accounts of other updated price feeds
+    ///    and history accounts of updated price feeds [writable]
+    ///    and updater allowlist accounts of updated price feeds [readonly]
+    ///
+    /// The updater allowlists and the paused flags are checked as in `Update`.
     UpdateEcdsa = 2,
+    /// Change the configuration of a price feed.
+    /// Data: `SetConfigArgs`
+    /// Accounts:
+    /// 1. authority account [signer]
+    /// 2. example data account of the price feed [writable]
+    SetConfig = 3,
+    /// Propose a new authority of a price feed. The new authority takes effect once it
+    /// signs an `AcceptAuthority` instruction.
+    /// Data: `ProposeAuthorityArgs`
+    /// Accounts:
+    /// 1. authority account [signer]
+    /// 2. example data account of the price feed [writable]
+    ProposeAuthority = 4,
+    /// Become the authority of a price feed after being proposed with `ProposeAuthority`.
+    /// Data: none
+    /// Accounts:
+    /// 1. proposed authority account [signer]
+    /// 2. example data account of the price feed [writable]
+    AcceptAuthority = 5,
+    /// Close the data account of a price feed and its history and updater allowlist accounts,
+    /// if they exist, and reclaim their rent. The PDAs can be initialized again in a later
+    /// transaction.
+    /// Data: none
+    /// Accounts:
+    /// 1. authority account [signer]
+    /// 2. example data account of the price feed [writable]
+    /// 3. account receiving the lamports of the closed accounts [writable]
+    /// 4. example history account of the price feed, existing or not [writable]
+    /// 5. updater allowlist account of the price feed, existing or not [writable]
+    Close = 6,
+    /// Create the history PDA of a price feed. Once it exists, updates that include it
+    /// in their accounts append the new price to it.
+    /// Data: none
+    /// Accounts:
+    /// 1. payer account [writable]
+    /// 2. example data account of the price feed [readonly]
+    /// 3. example history account of the price feed [writable, non-existing]
+    /// 4. system program [readonly]
+    InitializeHistory = 7,
+    /// Compute the time-weighted average price of a price feed over a window ending at the
+    /// current cluster time. The result is returned as `TwapResult` in the return data.
+    /// Data: `GetTwapArgs`
+    /// Accounts:
+    /// 1. example history account of the price feed [readonly]
+    GetTwap = 8,
+    /// Update prices using several messages with native Solana signatures.
+    /// Data: `UpdateArgs` followed by signed Pyth Lazer updates, each one prefixed with
+    /// its length as a little-endian `u16`. The signature of the N-th update (starting
+    /// from 0) is checked by the signature `UpdateArgs::signature_index + N` of the
+    /// ed25519 instruction, unless it's discovered.
+    /// Accounts: same as `Update`.
+    UpdateBatch = 9,
+    /// Read the latest price of a price feed. The result is returned as `PriceResult`
+    /// in the return data. See the `cpi` module for calling it from another program.
+    /// A paused price feed is still readable, with `PriceResult::paused` set, but its price
+    /// must be within the maximum age like any other.
+    /// Data: `GetPriceArgs`
+    /// Accounts:
+    /// 1. example data account of the price feed [readonly]
+    GetPrice = 10,
+    /// Same as `Update` or `UpdateEcdsa`, depending on the format of the signed update.
+    /// The instructions sysvar account is required even for `LeEcdsaMessage` updates.
+    /// Data: `UpdateArgs` followed by a signed Pyth Lazer update in the `SolanaMessage`
+    /// or `LeEcdsaMessage` format.
+    /// Accounts: same as `Update`.
+    UpdateAuto = 11,
+    /// Replace the updater allowlist of a price feed, creating its account if needed.
+    /// While the allowlist is enabled, updates of the price feed must be paid by
+    /// one of the allowed updaters.
+    /// Data: `SetUpdaterAllowlistArgs`
+    /// Accounts:
+    /// 1. authority account [signer, writable]
+    /// 2. example data account of the price feed [writable]
+    /// 3. example updater allowlist account of the price feed [writable]
+    /// 4. system program [readonly]
+    SetUpdaterAllowlist = 12,
+    /// Pause or resume updates of a price feed. The stored price can still be read.
+    /// Data: `SetPausedArgs`
+    /// Accounts:
+    /// 1. authority account [signer]
+    /// 2. example data account of the price feed [writable]
+    SetPaused = 13,
 }
 
 /// Inputs to the `Initialize` instruction.
@@ -75,29 +185,580 @@ pub enum Instruction {
 pub struct InitializeArgs {
     /// ID of the price feed that this contract tracks.
     pub price_feed_id: u32,
+    /// Maximum age of an accepted update relative to the cluster clock, in microseconds.
+    pub max_age_us: u64,
+    /// Set of channels that updates are accepted from, see `channel_bit`.
+    pub accepted_channels: u32,
+    /// Maximum price move of an update relative to the previous price, in basis points
+    /// per second elapsed between them, or 0 to accept any move.
+    pub max_deviation_bps_per_second: u32,
+    /// Contains a `DeviationMode` value.
+    pub deviation_mode: u8,
+    /// Account that is allowed to change the configuration of the price feed.
+    pub authority: Pubkey,
+}
+
+/// Inputs to the `SetConfig` instruction.
+#[derive(Debug, Clone, Copy, Zeroable, Pod)]
+#[repr(C, packed)]
+pub struct SetConfigArgs {
+    /// ID of the price feed tracked by the data account. It can't be changed because
+    /// it's a part of the data PDA seeds, but it must match the data account.
+    pub price_feed_id: u32,
+    /// Maximum age of an accepted update relative to the cluster clock, in microseconds.
+    pub max_age_us: u64,
+    /// Set of channels that updates are accepted from, see `channel_bit`.
+    pub accepted_channels: u32,
+    /// Maximum price move of an update relative to the previous price, in basis points
+    /// per second elapsed between them, or 0 to accept any move.
+    pub max_deviation_bps_per_second: u32,
+    /// Contains a `DeviationMode` value.
+    pub deviation_mode: u8,
+}
+
+/// Inputs to the `ProposeAuthority` instruction.
+#[derive(Debug, Clone, Copy, Zeroable, Pod)]
+#[repr(C, packed)]
+pub struct ProposeAuthorityArgs {
+    /// The proposed authority, or `Pubkey::default()` to cancel a previous proposal.
+    pub new_authority: Pubkey,
+}
+
+/// Maximum number of keys in an updater allowlist.
+pub const MAX_ALLOWED_UPDATERS: usize = 8;
+
+/// Inputs to the `SetUpdaterAllowlist` instruction.
+#[derive(Debug, Clone, Copy, Zeroable, Pod)]
+#[repr(C, packed)]
+pub struct SetUpdaterAllowlistArgs {
+    /// 1 if updates must be paid by one of `updaters`, 0 if anyone may update.
+    pub enabled: u8,
+    /// Number of valid keys in `updaters`, at most `MAX_ALLOWED_UPDATERS`.
+    /// Must not be 0 if the allowlist is enabled.
+    pub len: u8,
+    /// Allowed updaters, the keys after the first `len` are ignored.
+    pub updaters: [Pubkey; MAX_ALLOWED_UPDATERS],
+}
+
+/// Inputs to the `SetPaused` instruction.
+#[derive(Debug, Clone, Copy, Zeroable, Pod)]
+#[repr(C, packed)]
+pub struct SetPausedArgs {
+    /// 1 to pause updates, 0 to resume them.
+    pub paused: u8,
+}
+
+/// Inputs to the `GetTwap` instruction.
+#[derive(Debug, Clone, Copy, Zeroable, Pod)]
+#[repr(C, packed)]
+pub struct GetTwapArgs {
+    /// Length of the averaging window, in microseconds.
+    pub window_us: u64,
+}
+
+/// Return data of the `GetTwap` instruction.
+#[derive(Debug, Clone, Copy, Zeroable, Pod)]
+#[repr(C, packed)]
+pub struct TwapResult {
+    /// Time-weighted average price over the window.
+    pub price: i64,
+    /// Exponent of `price`.
+    pub exponent: i16,
+    /// Start of the window, in microseconds since the Unix epoch.
+    pub start_timestamp_us: u64,
+    /// End of the window (the cluster time), in microseconds since the Unix epoch.
+    pub end_timestamp_us: u64,
+}
+
+/// Inputs to the `GetPrice` instruction.
+#[derive(Debug, Clone, Copy, Zeroable, Pod)]
+#[repr(C, packed)]
+pub struct GetPriceArgs {
+    /// Maximum age of the price relative to the cluster clock, in microseconds.
+    pub max_age_us: u64,
+}
+
+/// Layout version of `PriceResult`.
+pub const PRICE_RESULT_VERSION: u8 = 2;
+
+/// Return data of the `GetPrice` instruction. The layout of a version never changes,
+/// new fields are appended in a new version. Readers must accept any version from 1 on,
+/// treat the fields missing from older versions as zero and ignore the bytes after
+/// the fields they know, as `cpi::decode_price` does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Zeroable, Pod)]
+#[repr(C, packed)]
+pub struct PriceResult {
+    /// `PRICE_RESULT_VERSION` of the program that returned the result.
+    pub version: u8,
+    /// ID of the price feed.
+    pub price_feed_id: u32,
+    /// Timestamp of the price, in microseconds since the Unix epoch.
+    pub timestamp_us: u64,
+    pub price: i64,
+    /// Exponent of the prices and the confidence.
+    pub exponent: i16,
+    pub best_bid_price: i64,
+    pub best_ask_price: i64,
+    pub confidence: i64,
+    pub publisher_count: u16,
+    /// Set of `property_flags` values that are present. Absent properties are 0.
+    pub present_properties: u8,
+    /// 1 if updates of the price feed are paused by its authority. Added in version 2.
+    pub paused: u8,
 }
 
-/// Inputs to the `Update` instruction. `UpdateArgs` must be followed by a signed Pyth Lazer message.
+/// Layout version of `UpdateArgs`. It's the first byte of the arguments so that clients
+/// built for another layout get `Error::UnsupportedVersion` instead of a misparse.
+pub const UPDATE_ARGS_VERSION: u8 = 1;
+
+/// Inputs to the `Update` and `UpdateEcdsa` instructions. `UpdateArgs` must be followed
+/// by a signed Pyth Lazer message.
+///
+/// The constraints are checked in addition to the configuration of the price feeds.
 #[derive(Debug, Clone, Copy, Zeroable, Pod)]
 #[repr(C, packed)]
 pub struct UpdateArgs {
-    /// Example argument
-    pub hello: u64,
+    /// Must be `UPDATE_ARGS_VERSION`.
+    pub version: u8,
+    /// What to do with feeds of the update that have no matching data account.
+    /// Contains a `UnmatchedFeedMode` value.
+    pub unmatched_feed_mode: u8,
+    /// ID of a price feed that the update must contain, or 0 for no constraint.
+    pub expected_price_feed_id: u32,
+    /// Minimum timestamp of the update, in microseconds since the Unix epoch.
+    pub min_timestamp_us: u64,
+    /// Maximum age of the update relative to the cluster clock, in microseconds,
+    /// or 0 for no constraint.
+    pub max_age_us: u64,
+    /// Index of the instruction to the built-in ed25519 program within the transaction,
+    /// or `DISCOVER_ED25519_INSTRUCTION` to find it by scanning the instructions sysvar.
+    /// Not used by `UpdateEcdsa`.
+    pub ed25519_instruction_index: u16,
+    /// Index of the signature of the update within the ed25519 instruction.
+    /// Not used by `UpdateEcdsa` or when the ed25519 instruction is discovered.
+    pub signature_index: u8,
+}
+
+/// Size of the length prefix of each message of `UpdateBatch`.
+pub const BATCH_MESSAGE_LEN_SIZE: usize = size_of::<u16>();
+
+/// Value of `UpdateArgs::ed25519_instruction_index` that makes the program find the ed25519
+/// instruction and the signature checking the update. This costs additional compute units
+/// for each instruction preceding the update.
+pub const DISCOVER_ED25519_INSTRUCTION: u16 = u16::MAX;
+
+impl UpdateArgs {
+    /// Returns arguments without additional constraints, expecting the signature to be
+    /// the first one of the first instruction of the transaction.
+    pub fn new(unmatched_feed_mode: UnmatchedFeedMode) -> Self {
+        Self {
+            version: UPDATE_ARGS_VERSION,
+            unmatched_feed_mode: unmatched_feed_mode as u8,
+            ..Self::zeroed()
+        }
+    }
+}
+
+/// Handling of the feeds contained in a Pyth Lazer update that don't have
+/// a matching data account passed to the instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, FromPrimitive)]
+pub enum UnmatchedFeedMode {
+    /// Fail the instruction.
+    Reject = 0,
+    /// Ignore the feed.
+    Skip = 1,
+}
+
+/// Handling of updates that move the price further than `State::max_deviation_bps_per_second`
+/// allows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, FromPrimitive)]
+pub enum DeviationMode {
+    /// Fail the instruction with `Error::DeviationExceeded`.
+    Reject = 0,
+    /// Keep the update in the pending slot of the data account instead of applying it.
+    /// It's confirmed by a later update that is within the allowed deviation from it.
+    Quarantine = 1,
+}
+
+/// How the signature of an update was verified.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, FromPrimitive)]
+pub enum VerificationPath {
+    /// `SolanaMessage` verified with an ed25519 instruction (`Update`, `UpdateBatch`).
+    Ed25519 = 0,
+    /// `LeEcdsaMessage` verified by the Pyth Lazer program (`UpdateEcdsa`).
+    /// `UpdateAuto` uses the path matching the format of the update.
+    Ecdsa = 1,
+}
+
+/// Discriminator preceding `PriceUpdateEvent` in the program logs.
+pub const PRICE_UPDATE_EVENT_DISCRIMINATOR: [u8; 8] = *b"lzrpxupd";
+
+/// Logged with `sol_log_data` for every price stored by an update instruction,
+/// as two fields: `PRICE_UPDATE_EVENT_DISCRIMINATOR` and the event.
+/// Quarantined updates are not logged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Zeroable, Pod)]
+#[repr(C, packed)]
+pub struct PriceUpdateEvent {
+    pub price_feed_id: u32,
+    pub price: i64,
+    /// Exponent stored for the price feed, 0 if it never received one.
+    pub exponent: i16,
+    pub timestamp_us: u64,
+    pub channel_id: u8,
+    /// `VerificationPath` of the update.
+    pub verification_path: u8,
 }
 
 /// Content of the data PDA.
+///
+/// Properties other than the price are optional in Pyth Lazer updates. If a property was not
+/// present in the latest update (or its value was `None`), its bit is unset in
+/// `present_properties` and the corresponding field is zero.
 #[derive(Debug, Clone, Copy, Zeroable, Pod)]
 #[repr(C, packed)]
 pub struct State {
+    /// Always `STATE_DISCRIMINATOR` in an initialized data account.
+    pub discriminator: [u8; 8],
     /// ID of the price feed that this contract tracks.
     pub price_feed: u32,
     /// Latest observed timestamp for this price feed.
     pub latest_timestamp: u64,
     /// Latest observed price for this price feed.
     pub latest_price: i64,
+    /// Latest observed exponent of the prices and the confidence for this price feed.
+    pub latest_exponent: i16,
+    /// Latest observed best bid price for this price feed.
+    pub latest_best_bid_price: i64,
+    /// Latest observed best ask price for this price feed.
+    pub latest_best_ask_price: i64,
+    /// Latest observed confidence interval for this price feed.
+    pub latest_confidence: i64,
+    /// Latest observed number of publishers contributing to the price.
+    pub latest_publisher_count: u16,
+    /// Set of `property_flags` values that were present in the latest update.
+    pub present_properties: u8,
+    /// Maximum age of an accepted update relative to the cluster clock, in microseconds.
+    pub max_age_us: u64,
+    /// Set of channels that updates are accepted from, see `channel_bit`.
+    pub accepted_channels: u32,
+    /// Account that is allowed to change the configuration of this price feed.
+    pub authority: Pubkey,
+    /// Authority proposed by `ProposeAuthority` that hasn't accepted yet,
+    /// or `Pubkey::default()` if there is none.
+    pub pending_authority: Pubkey,
+    /// Maximum price move of an update relative to the previous price, in basis points
+    /// per second elapsed between them, or 0 to accept any move.
+    pub max_deviation_bps_per_second: u32,
+    /// Contains a `DeviationMode` value.
+    pub deviation_mode: u8,
+    /// Timestamp of the quarantined update, or 0 if there is none.
+    pub pending_timestamp: u64,
+    /// Price of the quarantined update.
+    pub pending_price: i64,
+    /// Bump seed of the data PDA.
+    pub bump: u8,
+    /// 1 if updates must be paid by a key of the updater allowlist of this price feed.
+    pub updater_allowlist_enabled: u8,
+    /// 1 if updates of this price feed are paused by the authority.
+    pub paused: u8,
+}
+
+/// Bits of `State::present_properties`.
+pub mod property_flags {
+    pub const EXPONENT: u8 = 1 << 0;
+    pub const BEST_BID_PRICE: u8 = 1 << 1;
+    pub const BEST_ASK_PRICE: u8 = 1 << 2;
+    pub const CONFIDENCE: u8 = 1 << 3;
+    pub const PUBLISHER_COUNT: u8 = 1 << 4;
+}
+
+/// Value of `State::discriminator` in an initialized data account.
+pub const STATE_DISCRIMINATOR: [u8; 8] = *b"lzrstate";
+
+/// Value written over the discriminator of the accounts closed by `Close`. They are garbage
+/// collected at the end of the transaction, but until then they must not be usable by other
+/// instructions.
+pub const CLOSED_ACCOUNT_DISCRIMINATOR: [u8; 8] = [255; 8];
+
+impl State {
+    /// Interprets the data of an initialized data account.
+    pub fn load(data: &[u8]) -> Result<&Self, ProgramError> {
+        let state = try_from_bytes::<Self>(data).map_err(|_| ProgramError::InvalidAccountData)?;
+        state.check_discriminator()?;
+        Ok(state)
+    }
+
+    /// Interprets the data of an initialized data account for modification.
+    pub fn load_mut(data: &mut [u8]) -> Result<&mut Self, ProgramError> {
+        let state =
+            try_from_bytes_mut::<Self>(data).map_err(|_| ProgramError::InvalidAccountData)?;
+        state.check_discriminator()?;
+        Ok(state)
+    }
+
+    fn check_discriminator(&self) -> ProgramResult {
+        match self.discriminator {
+            STATE_DISCRIMINATOR => Ok(()),
+            CLOSED_ACCOUNT_DISCRIMINATOR => Err(Error::AccountClosed.into()),
+            _ => Err(ProgramError::InvalidAccountData),
+        }
+    }
+
+    fn property<T>(&self, flag: u8, value: T) -> Option<T> {
+        (self.present_properties & flag != 0).then_some(value)
+    }
+
+    pub fn exponent(&self) -> Option<i16> {
+        self.property(property_flags::EXPONENT, self.latest_exponent)
+    }
+
+    pub fn best_bid_price(&self) -> Option<i64> {
+        self.property(property_flags::BEST_BID_PRICE, self.latest_best_bid_price)
+    }
+
+    pub fn best_ask_price(&self) -> Option<i64> {
+        self.property(property_flags::BEST_ASK_PRICE, self.latest_best_ask_price)
+    }
+
+    pub fn confidence(&self) -> Option<i64> {
+        self.property(property_flags::CONFIDENCE, self.latest_confidence)
+    }
+
+    pub fn publisher_count(&self) -> Option<u16> {
+        self.property(property_flags::PUBLISHER_COUNT, self.latest_publisher_count)
+    }
+
+    /// Checks whether `apply_update` accepts an update of this price feed from `data` with
+    /// `price`, `age_us` being its age relative to the cluster clock. Returns `false` if the
+    /// update would be quarantined instead of stored. The state is not modified.
+    pub fn check_update(
+        &self,
+        data: &PayloadData,
+        age_us: u64,
+        price: Option<i64>,
+    ) -> Result<bool, ProgramError> {
+        if self.accepted_channels & channel_bit(data.channel_id) == 0 {
+            return Err(Error::WrongChannel.into());
+        }
+        if age_us > self.max_age_us {
+            return Err(Error::PriceStale.into());
+        }
+        if data.timestamp_us.0 <= self.latest_timestamp.max(self.pending_timestamp) {
+            return Err(Error::NonMonotonicTimestamp.into());
+        }
+        let price = price.ok_or(Error::MissingPrice)?;
+
+        // There is nothing to compare the first price of a feed to.
+        let update = (data.timestamp_us.0, price);
+        if self.latest_timestamp == 0
+            || is_within_deviation(
+                self.max_deviation_bps_per_second,
+                (self.latest_timestamp, self.latest_price),
+                update,
+            )
+        {
+            return Ok(true);
+        }
+        let is_confirmed = self.pending_timestamp != 0
+            && is_within_deviation(
+                self.max_deviation_bps_per_second,
+                (self.pending_timestamp, self.pending_price),
+                update,
+            );
+        match DeviationMode::from_u8(self.deviation_mode) {
+            Some(DeviationMode::Reject) => Err(Error::DeviationExceeded.into()),
+            Some(DeviationMode::Quarantine) => Ok(is_confirmed),
+            None => Err(ProgramError::InvalidAccountData),
+        }
+    }
+}
+
+/// Number of prices kept by a history account.
+pub const HISTORY_CAPACITY: usize = 128;
+
+/// Value of `History::discriminator` in an initialized history account.
+pub const HISTORY_DISCRIMINATOR: [u8; 8] = *b"lzrhstry";
+
+/// A price stored in a history account.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Zeroable, Pod)]
+#[repr(C, packed)]
+pub struct HistoryEntry {
+    /// Timestamp of the update.
+    pub timestamp_us: u64,
+    /// Price of the update.
+    pub price: i64,
+    /// Exponent of the update, or 0 if the update didn't contain it.
+    pub exponent: i16,
+}
+
+/// Content of the history PDA: a ring buffer of the latest `HISTORY_CAPACITY` prices
+/// of a price feed.
+#[derive(Debug, Clone, Copy, Zeroable, Pod)]
+#[repr(C, packed)]
+pub struct History {
+    /// Always `HISTORY_DISCRIMINATOR` in an initialized history account.
+    pub discriminator: [u8; 8],
+    /// ID of the price feed that this history belongs to.
+    pub price_feed: u32,
+    /// Index in `entries` that the next price is written to.
+    pub next_index: u32,
+    /// Number of valid entries, at most `HISTORY_CAPACITY`.
+    pub len: u32,
+    /// Bump seed of the history PDA.
+    pub bump: u8,
+    pub entries: [HistoryEntry; HISTORY_CAPACITY],
+}
+
+impl History {
+    /// Interprets the data of an initialized history account.
+    pub fn load(data: &[u8]) -> Result<&Self, ProgramError> {
+        let history = try_from_bytes::<Self>(data).map_err(|_| ProgramError::InvalidAccountData)?;
+        if history.discriminator != HISTORY_DISCRIMINATOR {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(history)
+    }
+
+    /// Interprets the data of an initialized history account for modification.
+    pub fn load_mut(data: &mut [u8]) -> Result<&mut Self, ProgramError> {
+        let history =
+            try_from_bytes_mut::<Self>(data).map_err(|_| ProgramError::InvalidAccountData)?;
+        if history.discriminator != HISTORY_DISCRIMINATOR {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(history)
+    }
+
+    /// Returns the stored prices from the oldest to the newest.
+    pub fn entries(&self) -> impl Iterator<Item = HistoryEntry> + '_ {
+        let len = self.len as usize;
+        let first = (self.next_index as usize + HISTORY_CAPACITY - len) % HISTORY_CAPACITY;
+        (0..len).map(move |i| self.entries[(first + i) % HISTORY_CAPACITY])
+    }
+
+    /// Adds a price, overwriting the oldest one if the buffer is full.
+    pub fn push(&mut self, entry: HistoryEntry) {
+        let index = self.next_index as usize % HISTORY_CAPACITY;
+        self.entries[index] = entry;
+        self.next_index = ((index + 1) % HISTORY_CAPACITY) as u32;
+        self.len = (self.len as usize + 1).min(HISTORY_CAPACITY) as u32;
+    }
+
+    /// Computes the time-weighted average price over `[start_us, end_us)`. Each price is
+    /// weighted by the time until the next price (or `end_us` for the latest one).
+    /// Returns the price and its exponent.
+    pub fn twap(&self, start_us: u64, end_us: u64) -> Result<(i64, i16), ProgramError> {
+        if start_us >= end_us {
+            return Err(ProgramError::InvalidArgument);
+        }
+        let entries: Vec<_> = self.entries().collect();
+        // A price must be known at the start of the window.
+        if entries
+            .first()
+            .map_or(true, |entry| entry.timestamp_us > start_us)
+        {
+            return Err(Error::InsufficientHistory.into());
+        }
+
+        let mut exponent = None;
+        let mut weighted_sum = 0i128;
+        for (index, entry) in entries.iter().enumerate() {
+            let from = entry.timestamp_us.max(start_us);
+            let to = entries
+                .get(index + 1)
+                .map_or(end_us, |next| next.timestamp_us)
+                .min(end_us);
+            if to <= from {
+                continue;
+            }
+            if *exponent.get_or_insert(entry.exponent) != entry.exponent {
+                return Err(Error::ExponentMismatch.into());
+            }
+            weighted_sum += i128::from(entry.price) * i128::from(to - from);
+        }
+        let price = i64::try_from(weighted_sum / i128::from(end_us - start_us))
+            .map_err(|_| ProgramError::ArithmeticOverflow)?;
+        Ok((price, exponent.ok_or(Error::InsufficientHistory)?))
+    }
+}
+
+/// Value of `UpdaterAllowlist::discriminator` in an initialized updater allowlist account.
+pub const UPDATER_ALLOWLIST_DISCRIMINATOR: [u8; 8] = *b"lzrallow";
+
+/// Content of the updater allowlist PDA of a price feed. It's only enforced while
+/// `State::updater_allowlist_enabled` is set.
+#[derive(Debug, Clone, Copy, Zeroable, Pod)]
+#[repr(C, packed)]
+pub struct UpdaterAllowlist {
+    /// Always `UPDATER_ALLOWLIST_DISCRIMINATOR` in an initialized updater allowlist account.
+    pub discriminator: [u8; 8],
+    /// ID of the price feed that this allowlist belongs to.
+    pub price_feed: u32,
+    /// Bump seed of the updater allowlist PDA.
+    pub bump: u8,
+    /// Number of valid keys in `updaters`.
+    pub len: u8,
+    pub updaters: [Pubkey; MAX_ALLOWED_UPDATERS],
+}
+
+impl UpdaterAllowlist {
+    /// Interprets the data of an initialized updater allowlist account.
+    pub fn load(data: &[u8]) -> Result<&Self, ProgramError> {
+        let allowlist =
+            try_from_bytes::<Self>(data).map_err(|_| ProgramError::InvalidAccountData)?;
+        if allowlist.discriminator != UPDATER_ALLOWLIST_DISCRIMINATOR {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(allowlist)
+    }
+
+    /// Returns the allowed updaters.
+    pub fn updaters(&self) -> &[Pubkey] {
+        &self.updaters[..usize::from(self.len).min(MAX_ALLOWED_UPDATERS)]
+    }
+}
+
+/// Returns the bit representing `channel_id` in a set of accepted channels,
+/// e.g. `InitializeArgs::accepted_channels`.
+pub fn channel_bit(channel_id: ChannelId) -> u32 {
+    1u32.checked_shl(channel_id.0.into()).unwrap_or(0)
 }
 
-const DATA_PDA_SEED: &[u8] = b"data";
+/// Maximum amount of time an update timestamp may be ahead of the cluster clock, in microseconds.
+/// The cluster clock only has a resolution of one second and usually lags behind the wall clock.
+pub const MAX_FUTURE_SKEW_US: u64 = 10_000_000;
+
+/// Seed prefix of the data PDA. The full seeds are `[DATA_PDA_SEED, price_feed_id.to_le_bytes()]`.
+pub const DATA_PDA_SEED: &[u8] = b"data";
+
+/// Returns the address and bump seed of the data PDA that tracks `price_feed_id`.
+pub fn find_data_pda(program_id: &Pubkey, price_feed_id: u32) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[DATA_PDA_SEED, &price_feed_id.to_le_bytes()], program_id)
+}
+
+/// Seed prefix of the history PDA. The full seeds are
+/// `[HISTORY_PDA_SEED, price_feed_id.to_le_bytes()]`.
+pub const HISTORY_PDA_SEED: &[u8] = b"history";
+
+/// Returns the address and bump seed of the history PDA of `price_feed_id`.
+pub fn find_history_pda(program_id: &Pubkey, price_feed_id: u32) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[HISTORY_PDA_SEED, &price_feed_id.to_le_bytes()],
+        program_id,
+    )
+}
+
+/// Seed prefix of the updater allowlist PDA. The full seeds are
+/// `[UPDATER_ALLOWLIST_PDA_SEED, price_feed_id.to_le_bytes()]`.
+pub const UPDATER_ALLOWLIST_PDA_SEED: &[u8] = b"updaters";
+
+/// Returns the address and bump seed of the updater allowlist PDA of `price_feed_id`.
+pub fn find_updater_allowlist_pda(program_id: &Pubkey, price_feed_id: u32) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[UPDATER_ALLOWLIST_PDA_SEED, &price_feed_id.to_le_bytes()],
+        program_id,
+    )
+}
 
 /// Program entrypoint's implementation.
 pub fn process_instruction(
@@ -121,6 +782,37 @@ pub fn process_instruction(
         Instruction::UpdateEcdsa => {
             process_update_ecdsa_instruction(program_id, accounts, instruction_args)
         }
+        Instruction::SetConfig => {
+            process_set_config_instruction(program_id, accounts, instruction_args)
+        }
+        Instruction::ProposeAuthority => {
+            process_propose_authority_instruction(program_id, accounts, instruction_args)
+        }
+        Instruction::AcceptAuthority => {
+            process_accept_authority_instruction(program_id, accounts, instruction_args)
+        }
+        Instruction::Close => process_close_instruction(program_id, accounts, instruction_args),
+        Instruction::InitializeHistory => {
+            process_initialize_history_instruction(program_id, accounts, instruction_args)
+        }
+        Instruction::GetTwap => {
+            process_get_twap_instruction(program_id, accounts, instruction_args)
+        }
+        Instruction::UpdateBatch => {
+            process_update_batch_instruction(program_id, accounts, instruction_args)
+        }
+        Instruction::GetPrice => {
+            process_get_price_instruction(program_id, accounts, instruction_args)
+        }
+        Instruction::UpdateAuto => {
+            process_update_auto_instruction(program_id, accounts, instruction_args)
+        }
+        Instruction::SetUpdaterAllowlist => {
+            process_set_updater_allowlist_instruction(program_id, accounts, instruction_args)
+        }
+        Instruction::SetPaused => {
+            process_set_paused_instruction(program_id, accounts, instruction_args)
+        }
     }
 }
 
@@ -129,21 +821,26 @@ pub fn process_initialize_instruction(
     accounts: &[AccountInfo],
     instruction_args: &[u8],
 ) -> ProgramResult {
-    if accounts.len() != 3 {
+    if accounts.len() != 4 {
         return Err(ProgramError::NotEnoughAccountKeys);
     }
     let payer_account = &accounts[0];
     let data_account = &accounts[1];
     let system_program_account = &accounts[2];
+    let authority_account = &accounts[3];
+
+    let args = try_from_bytes::<InitializeArgs>(instruction_args)
+        .map_err(|_| ProgramError::InvalidInstructionData)?;
+    DeviationMode::from_u8(args.deviation_mode).ok_or(ProgramError::InvalidInstructionData)?;
+    // The authority must sign so that a price feed can't be assigned to a key without its consent.
+    verify_signer(authority_account, &args.authority)?;
+    let price_feed_id = args.price_feed_id;
 
-    let (data_pda_key, data_pda_bump_seed) =
-        Pubkey::find_program_address(&[DATA_PDA_SEED], program_id);
+    let (data_pda_key, data_pda_bump_seed) = find_data_pda(program_id, price_feed_id);
     if data_account.key != &data_pda_key {
-        return Err(ProgramError::InvalidAccountData);
+        return Err(Error::InvalidPda.into());
     }
 
-    let args = try_from_bytes::<InitializeArgs>(instruction_args)
-        .map_err(|_| ProgramError::InvalidInstructionData)?;
     let space = size_of::<State>();
     // Create the data PDA.
     let create_instruction = create_account(
@@ -160,16 +857,26 @@ pub fn process_initialize_instruction(
             data_account.clone(),
             system_program_account.clone(),
         ],
-        &[&[DATA_PDA_SEED, &[data_pda_bump_seed]]],
+        &[&[
+            DATA_PDA_SEED,
+            &price_feed_id.to_le_bytes(),
+            &[data_pda_bump_seed],
+        ]],
     )?;
     // Write config to the PDA.
     data_account
         .data
         .borrow_mut()
         .clone_from_slice(bytes_of(&State {
-            price_feed: args.price_feed_id,
-            latest_timestamp: 0,
-            latest_price: 0,
+            discriminator: STATE_DISCRIMINATOR,
+            price_feed: price_feed_id,
+            max_age_us: args.max_age_us,
+            accepted_channels: args.accepted_channels,
+            max_deviation_bps_per_second: args.max_deviation_bps_per_second,
+            deviation_mode: args.deviation_mode,
+            authority: args.authority,
+            bump: data_pda_bump_seed,
+            ..State::zeroed()
         }));
     Ok(())
 }
@@ -178,121 +885,599 @@ pub fn process_update_instruction(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
     instruction_args: &[u8],
+) -> ProgramResult {
+    process_single_update(
+        program_id,
+        accounts,
+        instruction_args,
+        7,
+        Some(VerificationPath::Ed25519),
+    )
+}
+
+pub fn process_update_batch_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_args: &[u8],
 ) -> ProgramResult {
     // Verify accounts passed to the instruction.
-    if accounts.len() != 7 {
+    if accounts.len() < 7 {
         return Err(ProgramError::NotEnoughAccountKeys);
     }
     let payer_account = &accounts[0];
     let data_account = &accounts[1];
-    let _pyth_program_account = &accounts[2];
-    let pyth_storage_account = &accounts[3];
-    let pyth_treasury_account = &accounts[4];
-    let system_program_account = &accounts[5];
-    let pyth_lazer_program_account = &accounts[6];
+    let (data_accounts, history_accounts) =
+        verify_update_accounts(program_id, payer_account, data_account, &accounts[7..])?;
 
-    let (data_pda_key, _data_pda_bump_seed) =
-        Pubkey::find_program_address(&[DATA_PDA_SEED], program_id);
-    if data_account.key != &data_pda_key {
-        return Err(ProgramError::InvalidAccountData);
+    let (update_args, mut messages) = parse_update_args(instruction_args)?;
+    // 1 byte is the instruction type.
+    let mut message_offset = 1 + size_of::<UpdateArgs>();
+    let mut payloads = Vec::new();
+    while !messages.is_empty() {
+        let len: usize = messages
+            .get(..BATCH_MESSAGE_LEN_SIZE)
+            .map(|len| u16::from_le_bytes([len[0], len[1]]).into())
+            .ok_or(Error::InvalidMessage)?;
+        let pyth_message = messages
+            .get(BATCH_MESSAGE_LEN_SIZE..BATCH_MESSAGE_LEN_SIZE + len)
+            .ok_or(Error::InvalidMessage)?;
+        message_offset += BATCH_MESSAGE_LEN_SIZE;
+        let message_index = payloads
+            .len()
+            .try_into()
+            .map_err(|_| ProgramError::InvalidInstructionData)?;
+        payloads.push(verify_solana_message(
+            &accounts[..7],
+            update_args,
+            pyth_message,
+            message_offset,
+            message_index,
+        )?);
+        message_offset += len;
+        messages = &messages[BATCH_MESSAGE_LEN_SIZE + len..];
     }
-
-    // Parse instruction data.
-    let update_args = instruction_args
-        .get(..size_of::<UpdateArgs>())
-        .and_then(|data| try_from_bytes::<UpdateArgs>(data).ok())
-        .ok_or(ProgramError::InvalidInstructionData)?;
-    if update_args.hello != 42 {
-        return Err(ProgramError::InvalidInstructionData);
+    if payloads.is_empty() {
+        return Err(Error::InvalidMessage.into());
     }
-    let pyth_message = &instruction_args[size_of::<UpdateArgs>()..];
-
-    // We expect the instruction to the built-in ed25519 program to be
-    // the first instruction within the transaction.
-    let ed25519_instruction_index = 0;
-    // We expect our signature to be the first (and only) signature to be checked
-    // by the built-in ed25519 program within the transaction.
-    let signature_index = 0;
-
-    // Verify Lazer signature.
-    invoke(
-        &ProgramInstruction::new_with_bytes(
-            pyth_lazer_solana_contract::ID,
-            &VerifyMessage {
-                message_data: pyth_message.to_vec(),
-                ed25519_instruction_index,
-                signature_index,
-            }
-            .data(),
-            vec![
-                AccountMeta::new(*payer_account.key, true),
-                AccountMeta::new_readonly(*pyth_storage_account.key, false),
-                AccountMeta::new(*pyth_treasury_account.key, false),
-                AccountMeta::new_readonly(*system_program_account.key, false),
-                AccountMeta::new_readonly(*pyth_lazer_program_account.key, false),
-            ],
-        ),
-        &[
-            payer_account.clone(),
-            pyth_storage_account.clone(),
-            pyth_treasury_account.clone(),
-            system_program_account.clone(),
-            pyth_lazer_program_account.clone(),
-        ],
-    )?;
 
-    let pyth_message = SolanaMessage::deserialize_slice(pyth_message)
-        .map_err(|_| ProgramError::InvalidInstructionData)?;
+    apply_update(
+        &data_accounts,
+        &history_accounts,
+        &payloads,
+        update_args,
+        VerificationPath::Ed25519,
+    )
+}
 
-    // Deserialize and use the payload.
-    let data = PayloadData::deserialize_slice_le(&pyth_message.payload)
-        .map_err(|_| ProgramError::InvalidInstructionData)?;
+pub fn process_update_ecdsa_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_args: &[u8],
+) -> ProgramResult {
+    process_single_update(
+        program_id,
+        accounts,
+        instruction_args,
+        6,
+        Some(VerificationPath::Ecdsa),
+    )
+}
 
-    apply_update(data_account, &data)
+pub fn process_update_auto_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_args: &[u8],
+) -> ProgramResult {
+    process_single_update(program_id, accounts, instruction_args, 7, None)
 }
 
-pub fn process_update_ecdsa_instruction(
+/// Applies an instruction containing a single signed update. The first `fixed_accounts`
+/// accounts are the ones of `Update` or `UpdateEcdsa`, the data and history accounts follow.
+/// If `verification_path` is `None`, it's derived from the format of the update.
+fn process_single_update(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
     instruction_args: &[u8],
+    fixed_accounts: usize,
+    verification_path: Option<VerificationPath>,
 ) -> ProgramResult {
     // Verify accounts passed to the instruction.
-    if accounts.len() != 6 {
+    if accounts.len() < fixed_accounts {
         return Err(ProgramError::NotEnoughAccountKeys);
     }
     let payer_account = &accounts[0];
     let data_account = &accounts[1];
-    let _pyth_program_account = &accounts[2];
-    let pyth_storage_account = &accounts[3];
-    let pyth_treasury_account = &accounts[4];
-    let system_program_account = &accounts[5];
+    let (data_accounts, history_accounts) = verify_update_accounts(
+        program_id,
+        payer_account,
+        data_account,
+        &accounts[fixed_accounts..],
+    )?;
 
-    let (data_pda_key, _data_pda_bump_seed) =
-        Pubkey::find_program_address(&[DATA_PDA_SEED], program_id);
-    if data_account.key != &data_pda_key {
-        return Err(ProgramError::InvalidAccountData);
+    let (update_args, pyth_message) = parse_update_args(instruction_args)?;
+    let verification_path = match verification_path {
+        Some(verification_path) => verification_path,
+        None => message_verification_path(pyth_message)?,
+    };
+    let data = match verification_path {
+        VerificationPath::Ed25519 => {
+            // 1 byte is the instruction type.
+            let message_offset = 1 + size_of::<UpdateArgs>();
+            verify_solana_message(&accounts[..7], update_args, pyth_message, message_offset, 0)?
+        }
+        VerificationPath::Ecdsa => verify_ecdsa_message(&accounts[..6], pyth_message)?,
+    };
+
+    apply_update(
+        &data_accounts,
+        &history_accounts,
+        &[data],
+        update_args,
+        verification_path,
+    )
+}
+
+/// Returns how a signed update has to be verified, based on its format magic.
+fn message_verification_path(pyth_message: &[u8]) -> Result<VerificationPath, ProgramError> {
+    match message_magic(pyth_message)? {
+        SOLANA_FORMAT_MAGIC => Ok(VerificationPath::Ed25519),
+        LE_ECDSA_FORMAT_MAGIC => Ok(VerificationPath::Ecdsa),
+        _ => Err(Error::InvalidMessage.into()),
     }
+}
 
-    // Parse instruction data.
-    let update_args = instruction_args
-        .get(..size_of::<UpdateArgs>())
-        .and_then(|data| try_from_bytes::<UpdateArgs>(data).ok())
-        .ok_or(ProgramError::InvalidInstructionData)?;
-    if update_args.hello != 42 {
-        return Err(ProgramError::InvalidInstructionData);
+pub fn process_set_config_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_args: &[u8],
+) -> ProgramResult {
+    if accounts.len() != 2 {
+        return Err(ProgramError::NotEnoughAccountKeys);
     }
-    let pyth_message = &instruction_args[size_of::<UpdateArgs>()..];
+    let authority_account = &accounts[0];
+    let data_account = &accounts[1];
+    verify_data_accounts(program_id, iter::once(data_account))?;
 
-    // Verify Lazer signature.
-    invoke(
-        &ProgramInstruction::new_with_bytes(
-            pyth_lazer_solana_contract::ID,
-            &VerifyEcdsaMessage {
-                message_data: pyth_message.to_vec(),
-            }
-            .data(),
-            vec![
-                AccountMeta::new(*payer_account.key, true),
+    let args = try_from_bytes::<SetConfigArgs>(instruction_args)
+        .map_err(|_| ProgramError::InvalidInstructionData)?;
+    DeviationMode::from_u8(args.deviation_mode).ok_or(ProgramError::InvalidInstructionData)?;
+
+    let mut state_data = data_account.data.borrow_mut();
+    let state = State::load_mut(*state_data)?;
+    verify_signer(authority_account, &state.authority)?;
+
+    if args.price_feed_id != state.price_feed {
+        return Err(Error::WrongPriceFeed.into());
+    }
+    state.max_age_us = args.max_age_us;
+    state.accepted_channels = args.accepted_channels;
+    state.max_deviation_bps_per_second = args.max_deviation_bps_per_second;
+    state.deviation_mode = args.deviation_mode;
+    Ok(())
+}
+
+pub fn process_propose_authority_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_args: &[u8],
+) -> ProgramResult {
+    if accounts.len() != 2 {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    }
+    let authority_account = &accounts[0];
+    let data_account = &accounts[1];
+    verify_data_accounts(program_id, iter::once(data_account))?;
+
+    let args = try_from_bytes::<ProposeAuthorityArgs>(instruction_args)
+        .map_err(|_| ProgramError::InvalidInstructionData)?;
+
+    let mut state_data = data_account.data.borrow_mut();
+    let state = State::load_mut(*state_data)?;
+    verify_signer(authority_account, &state.authority)?;
+
+    state.pending_authority = args.new_authority;
+    Ok(())
+}
+
+pub fn process_accept_authority_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_args: &[u8],
+) -> ProgramResult {
+    if accounts.len() != 2 {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    }
+    let new_authority_account = &accounts[0];
+    let data_account = &accounts[1];
+    verify_data_accounts(program_id, iter::once(data_account))?;
+
+    if !instruction_args.is_empty() {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+
+    let mut state_data = data_account.data.borrow_mut();
+    let state = State::load_mut(*state_data)?;
+    if state.pending_authority == Pubkey::default() {
+        return Err(Error::Unauthorized.into());
+    }
+    verify_signer(new_authority_account, &state.pending_authority)?;
+
+    state.authority = state.pending_authority;
+    state.pending_authority = Pubkey::default();
+    Ok(())
+}
+
+pub fn process_close_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_args: &[u8],
+) -> ProgramResult {
+    if accounts.len() != 5 {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    }
+    let authority_account = &accounts[0];
+    let data_account = &accounts[1];
+    let destination_account = &accounts[2];
+    let history_account = &accounts[3];
+    let allowlist_account = &accounts[4];
+    let price_feed_id = verify_data_accounts(program_id, iter::once(data_account))?[0].0;
+
+    if !instruction_args.is_empty() {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    // The other accounts must go as well, otherwise a reinitialized price feed would inherit them.
+    if history_account.key != &find_history_pda(program_id, price_feed_id).0
+        || allowlist_account.key != &find_updater_allowlist_pda(program_id, price_feed_id).0
+    {
+        return Err(Error::InvalidPda.into());
+    }
+    if [data_account, history_account, allowlist_account]
+        .iter()
+        .any(|account| account.key == destination_account.key)
+    {
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    verify_signer(
+        authority_account,
+        &State::load(&data_account.data.borrow())?.authority,
+    )?;
+    close_account(data_account, destination_account)?;
+    for account in [history_account, allowlist_account] {
+        if account.owner == program_id {
+            close_account(account, destination_account)?;
+        }
+    }
+    Ok(())
+}
+
+/// Moves all lamports of `account` to `destination_account`.
+fn close_account(
+    account: &AccountInfo<'_>,
+    destination_account: &AccountInfo<'_>,
+) -> ProgramResult {
+    // The runtime deletes accounts without lamports at the end of the transaction.
+    // Until then, the account is marked as closed so that it can't be used again.
+    let mut data = account.data.borrow_mut();
+    data.fill(0);
+    data[..CLOSED_ACCOUNT_DISCRIMINATOR.len()].copy_from_slice(&CLOSED_ACCOUNT_DISCRIMINATOR);
+
+    let mut lamports = account.lamports.borrow_mut();
+    let mut destination_lamports = destination_account.lamports.borrow_mut();
+    **destination_lamports = destination_lamports
+        .checked_add(**lamports)
+        .ok_or(ProgramError::ArithmeticOverflow)?;
+    **lamports = 0;
+    Ok(())
+}
+
+pub fn process_initialize_history_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_args: &[u8],
+) -> ProgramResult {
+    if accounts.len() != 4 {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    }
+    let payer_account = &accounts[0];
+    let data_account = &accounts[1];
+    let history_account = &accounts[2];
+    let system_program_account = &accounts[3];
+    let price_feed_id = verify_data_accounts(program_id, iter::once(data_account))?[0].0;
+
+    if !instruction_args.is_empty() {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+
+    let (history_pda_key, history_pda_bump_seed) = find_history_pda(program_id, price_feed_id);
+    if history_account.key != &history_pda_key {
+        return Err(Error::InvalidPda.into());
+    }
+
+    let space = size_of::<History>();
+    // Create the history PDA.
+    let create_instruction = create_account(
+        payer_account.key,
+        history_account.key,
+        Rent::get()?.minimum_balance(space),
+        space as u64,
+        program_id,
+    );
+    invoke_signed(
+        &create_instruction,
+        &[
+            payer_account.clone(),
+            history_account.clone(),
+            system_program_account.clone(),
+        ],
+        &[&[
+            HISTORY_PDA_SEED,
+            &price_feed_id.to_le_bytes(),
+            &[history_pda_bump_seed],
+        ]],
+    )?;
+    let mut history_data = history_account.data.borrow_mut();
+    let history = try_from_bytes_mut::<History>(*history_data)
+        .map_err(|_| ProgramError::InvalidAccountData)?;
+    history.discriminator = HISTORY_DISCRIMINATOR;
+    history.price_feed = price_feed_id;
+    history.bump = history_pda_bump_seed;
+    Ok(())
+}
+
+pub fn process_set_updater_allowlist_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_args: &[u8],
+) -> ProgramResult {
+    if accounts.len() != 4 {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    }
+    let authority_account = &accounts[0];
+    let data_account = &accounts[1];
+    let allowlist_account = &accounts[2];
+    let system_program_account = &accounts[3];
+    let price_feed_id = verify_data_accounts(program_id, iter::once(data_account))?[0].0;
+
+    let args = try_from_bytes::<SetUpdaterAllowlistArgs>(instruction_args)
+        .map_err(|_| ProgramError::InvalidInstructionData)?;
+    let len = usize::from(args.len);
+    if args.enabled > 1 || len > MAX_ALLOWED_UPDATERS || (args.enabled == 1 && len == 0) {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+
+    let mut state_data = data_account.data.borrow_mut();
+    let state = State::load_mut(*state_data)?;
+    verify_signer(authority_account, &state.authority)?;
+
+    let (allowlist_pda_key, allowlist_pda_bump_seed) =
+        find_updater_allowlist_pda(program_id, price_feed_id);
+    if allowlist_account.key != &allowlist_pda_key {
+        return Err(Error::InvalidPda.into());
+    }
+    if allowlist_account.owner != program_id {
+        let space = size_of::<UpdaterAllowlist>();
+        // Create the updater allowlist PDA, paid by the authority.
+        let create_instruction = create_account(
+            authority_account.key,
+            allowlist_account.key,
+            Rent::get()?.minimum_balance(space),
+            space as u64,
+            program_id,
+        );
+        invoke_signed(
+            &create_instruction,
+            &[
+                authority_account.clone(),
+                allowlist_account.clone(),
+                system_program_account.clone(),
+            ],
+            &[&[
+                UPDATER_ALLOWLIST_PDA_SEED,
+                &price_feed_id.to_le_bytes(),
+                &[allowlist_pda_bump_seed],
+            ]],
+        )?;
+    }
+    let mut updaters = [Pubkey::default(); MAX_ALLOWED_UPDATERS];
+    updaters[..len].copy_from_slice(&{ args.updaters }[..len]);
+    allowlist_account
+        .data
+        .borrow_mut()
+        .copy_from_slice(bytes_of(&UpdaterAllowlist {
+            discriminator: UPDATER_ALLOWLIST_DISCRIMINATOR,
+            price_feed: price_feed_id,
+            bump: allowlist_pda_bump_seed,
+            len: args.len,
+            updaters,
+        }));
+    state.updater_allowlist_enabled = args.enabled;
+    Ok(())
+}
+
+pub fn process_set_paused_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_args: &[u8],
+) -> ProgramResult {
+    if accounts.len() != 2 {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    }
+    let authority_account = &accounts[0];
+    let data_account = &accounts[1];
+    verify_data_accounts(program_id, iter::once(data_account))?;
+
+    let args = try_from_bytes::<SetPausedArgs>(instruction_args)
+        .map_err(|_| ProgramError::InvalidInstructionData)?;
+    if args.paused > 1 {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+
+    let mut state_data = data_account.data.borrow_mut();
+    let state = State::load_mut(*state_data)?;
+    verify_signer(authority_account, &state.authority)?;
+    state.paused = args.paused;
+    Ok(())
+}
+
+pub fn process_get_twap_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_args: &[u8],
+) -> ProgramResult {
+    if accounts.len() != 1 {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    }
+    let history_account = &accounts[0];
+    verify_history_accounts(program_id, iter::once(history_account))?;
+
+    let args = try_from_bytes::<GetTwapArgs>(instruction_args)
+        .map_err(|_| ProgramError::InvalidInstructionData)?;
+
+    let end_timestamp_us = now_us()?;
+    let start_timestamp_us = end_timestamp_us
+        .checked_sub(args.window_us)
+        .ok_or(ProgramError::InvalidInstructionData)?;
+    let history_data = history_account.data.borrow();
+    let (price, exponent) =
+        History::load(&history_data)?.twap(start_timestamp_us, end_timestamp_us)?;
+    set_return_data(bytes_of(&TwapResult {
+        price,
+        exponent,
+        start_timestamp_us,
+        end_timestamp_us,
+    }));
+    Ok(())
+}
+
+pub fn process_get_price_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_args: &[u8],
+) -> ProgramResult {
+    if accounts.len() != 1 {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    }
+    let data_account = &accounts[0];
+    verify_data_accounts(program_id, iter::once(data_account))?;
+
+    let args = try_from_bytes::<GetPriceArgs>(instruction_args)
+        .map_err(|_| ProgramError::InvalidInstructionData)?;
+
+    let state_data = data_account.data.borrow();
+    let state = State::load(&state_data)?;
+    if state.latest_timestamp == 0 {
+        return Err(Error::MissingPrice.into());
+    }
+    if now_us()?.saturating_sub(state.latest_timestamp) > args.max_age_us {
+        return Err(Error::PriceStale.into());
+    }
+    set_return_data(bytes_of(&PriceResult {
+        version: PRICE_RESULT_VERSION,
+        price_feed_id: state.price_feed,
+        timestamp_us: state.latest_timestamp,
+        price: state.latest_price,
+        exponent: state.latest_exponent,
+        best_bid_price: state.latest_best_bid_price,
+        best_ask_price: state.latest_best_ask_price,
+        confidence: state.latest_confidence,
+        publisher_count: state.latest_publisher_count,
+        present_properties: state.present_properties,
+        paused: state.paused,
+    }));
+    Ok(())
+}
+
+/// Parses the `UpdateArgs` at the start of the instruction data.
+/// Returns them together with the Pyth Lazer message that follows.
+fn parse_update_args(instruction_args: &[u8]) -> Result<(&UpdateArgs, &[u8]), ProgramError> {
+    // Check the version first, older layouts may have a different size.
+    if instruction_args.first() != Some(&UPDATE_ARGS_VERSION) {
+        return Err(Error::UnsupportedVersion.into());
+    }
+    let update_args = instruction_args
+        .get(..size_of::<UpdateArgs>())
+        .and_then(|data| try_from_bytes::<UpdateArgs>(data).ok())
+        .ok_or(ProgramError::InvalidInstructionData)?;
+    UnmatchedFeedMode::from_u8(update_args.unmatched_feed_mode)
+        .ok_or(ProgramError::InvalidInstructionData)?;
+    Ok((update_args, &instruction_args[size_of::<UpdateArgs>()..]))
+}
+
+/// Length of the magic, the signature and the public key preceding the payload size
+/// in a `SolanaMessage`.
+const SOLANA_HEADER_LEN: usize = 4 + 64 + 32;
+/// Length of the magic, the signature and the recovery ID preceding the payload size
+/// in a `LeEcdsaMessage`.
+const LE_ECDSA_HEADER_LEN: usize = 4 + 64 + 1;
+
+/// Returns the format magic at the start of a signed update.
+fn message_magic(pyth_message: &[u8]) -> Result<u32, ProgramError> {
+    pyth_message
+        .get(..size_of::<u32>())
+        .map(|magic| u32::from_le_bytes([magic[0], magic[1], magic[2], magic[3]]))
+        .ok_or(Error::InvalidMessage.into())
+}
+
+/// Returns the payload of a signed update with the given format magic and header length,
+/// borrowed from the update instead of being copied like `SolanaMessage::deserialize_slice` does.
+fn message_payload(
+    pyth_message: &[u8],
+    magic: u32,
+    header_len: usize,
+) -> Result<&[u8], ProgramError> {
+    if message_magic(pyth_message)? != magic {
+        return Err(Error::InvalidMessage.into());
+    }
+    let payload_len: usize = pyth_message
+        .get(header_len..header_len + size_of::<u16>())
+        .map(|len| u16::from_le_bytes([len[0], len[1]]).into())
+        .ok_or(Error::InvalidMessage)?;
+    let payload_start = header_len + size_of::<u16>();
+    pyth_message
+        .get(payload_start..payload_start + payload_len)
+        .ok_or(Error::InvalidMessage.into())
+}
+
+/// Serializes the arguments of a Pyth Lazer instruction that starts with the message
+/// (`VerifyMessage` or `VerifyEcdsaMessage`) directly from `pyth_message`, without
+/// copying it into the instruction struct first. `other_args` are the serialized
+/// arguments following the message.
+fn message_instruction_data(
+    discriminator: &[u8],
+    pyth_message: &[u8],
+    other_args: &[u8],
+) -> Result<Vec<u8>, ProgramError> {
+    let message_len = u32::try_from(pyth_message.len()).map_err(|_| Error::InvalidMessage)?;
+    let mut data = Vec::with_capacity(
+        discriminator.len() + size_of::<u32>() + pyth_message.len() + other_args.len(),
+    );
+    data.extend_from_slice(discriminator);
+    data.extend_from_slice(&message_len.to_le_bytes());
+    data.extend_from_slice(pyth_message);
+    data.extend_from_slice(other_args);
+    Ok(data)
+}
+
+/// Verifies the signature of an update in the `LeEcdsaMessage` format with the Pyth Lazer
+/// program and parses it. `accounts` are the first 6 accounts of `UpdateEcdsa`.
+fn verify_ecdsa_message(
+    accounts: &[AccountInfo],
+    pyth_message: &[u8],
+) -> Result<PayloadData, ProgramError> {
+    let payer_account = &accounts[0];
+    let _pyth_program_account = &accounts[2];
+    let pyth_storage_account = &accounts[3];
+    let pyth_treasury_account = &accounts[4];
+    let system_program_account = &accounts[5];
+
+    // Verify Lazer signature.
+    invoke(
+        &ProgramInstruction::new_with_bytes(
+            pyth_lazer_solana_contract::ID,
+            &message_instruction_data(&VerifyEcdsaMessage::DISCRIMINATOR, pyth_message, &[])?,
+            vec![
+                AccountMeta::new(*payer_account.key, true),
                 AccountMeta::new_readonly(*pyth_storage_account.key, false),
                 AccountMeta::new(*pyth_treasury_account.key, false),
                 AccountMeta::new_readonly(*system_program_account.key, false),
@@ -304,41 +1489,501 @@ pub fn process_update_ecdsa_instruction(
             pyth_treasury_account.clone(),
             system_program_account.clone(),
         ],
+    )
+    .map_err(|_| Error::VerificationFailed)?;
+
+    // Deserialize the payload.
+    let payload = message_payload(pyth_message, LE_ECDSA_FORMAT_MAGIC, LE_ECDSA_HEADER_LEN)?;
+    PayloadData::deserialize_slice_le(payload).map_err(|_| Error::InvalidMessage.into())
+}
+
+/// Verifies a Pyth Lazer message in the Solana format located at `message_offset` within the data
+/// of the current instruction, and returns its payload. `accounts` are the first 7 accounts of
+/// the `Update` instruction. `message_index` is the position of the message within the
+/// instruction, which is added to `UpdateArgs::signature_index`.
+fn verify_solana_message(
+    accounts: &[AccountInfo],
+    update_args: &UpdateArgs,
+    pyth_message: &[u8],
+    message_offset: usize,
+    message_index: u8,
+) -> Result<PayloadData, ProgramError> {
+    let payer_account = &accounts[0];
+    let _pyth_program_account = &accounts[2];
+    let pyth_storage_account = &accounts[3];
+    let pyth_treasury_account = &accounts[4];
+    let system_program_account = &accounts[5];
+    let instructions_sysvar_account = &accounts[6];
+
+    // The caller specifies where the built-in ed25519 program checks the signature
+    // within the transaction, unless it asks us to find it.
+    let (ed25519_instruction_index, signature_index) =
+        if update_args.ed25519_instruction_index == DISCOVER_ED25519_INSTRUCTION {
+            find_ed25519_signature(instructions_sysvar_account, pyth_message, message_offset)?
+        } else {
+            (
+                update_args.ed25519_instruction_index,
+                update_args
+                    .signature_index
+                    .checked_add(message_index)
+                    .ok_or(ProgramError::InvalidInstructionData)?,
+            )
+        };
+
+    // Verify Lazer signature.
+    invoke(
+        &ProgramInstruction::new_with_bytes(
+            pyth_lazer_solana_contract::ID,
+            &message_instruction_data(
+                &VerifyMessage::DISCRIMINATOR,
+                pyth_message,
+                &[
+                    &ed25519_instruction_index.to_le_bytes()[..],
+                    &[signature_index],
+                ]
+                .concat(),
+            )?,
+            vec![
+                AccountMeta::new(*payer_account.key, true),
+                AccountMeta::new_readonly(*pyth_storage_account.key, false),
+                AccountMeta::new(*pyth_treasury_account.key, false),
+                AccountMeta::new_readonly(*system_program_account.key, false),
+                AccountMeta::new_readonly(*instructions_sysvar_account.key, false),
+            ],
+        ),
+        &[
+            payer_account.clone(),
+            pyth_storage_account.clone(),
+            pyth_treasury_account.clone(),
+            system_program_account.clone(),
+            instructions_sysvar_account.clone(),
+        ],
+    )
+    .map_err(|_| Error::VerificationFailed)?;
+
+    // Deserialize the payload.
+    let payload = message_payload(pyth_message, SOLANA_FORMAT_MAGIC, SOLANA_HEADER_LEN)?;
+    PayloadData::deserialize_slice_le(payload).map_err(|_| Error::InvalidMessage.into())
+}
+
+/// Finds the signature checking `pyth_message` (located at `message_offset` within the data of
+/// the current instruction) among the ed25519 instructions preceding the current instruction.
+/// Returns the index of the ed25519 instruction and the index of the signature within it.
+fn find_ed25519_signature(
+    instructions_sysvar_account: &AccountInfo<'_>,
+    pyth_message: &[u8],
+    message_offset: usize,
+) -> Result<(u16, u8), ProgramError> {
+    let current_index = load_current_index_checked(instructions_sysvar_account)?;
+    // `Ed25519SignatureOffsets::new` panics on a truncated message.
+    message_payload(pyth_message, SOLANA_FORMAT_MAGIC, SOLANA_HEADER_LEN)?;
+    let expected_offsets = Ed25519SignatureOffsets::new(
+        pyth_message,
+        current_index,
+        message_offset
+            .try_into()
+            .map_err(|_| ProgramError::InvalidInstructionData)?,
+    );
+
+    for index in 0..current_index {
+        let instruction = load_instruction_at_checked(index.into(), instructions_sysvar_account)?;
+        if instruction.program_id != ed25519_program::ID {
+            continue;
+        }
+        // The ed25519 program arguments are the number of signatures, a padding byte
+        // and the offsets of each signature, possibly followed by the data of signatures
+        // that are stored in the instruction itself.
+        let Some(&num_signatures) = instruction.data.first() else {
+            continue;
+        };
+        let offsets_len = usize::from(num_signatures) * size_of::<Ed25519SignatureOffsets>();
+        let Some(offsets) = instruction
+            .data
+            .get(2..2 + offsets_len)
+            .and_then(|data| try_cast_slice::<u8, Ed25519SignatureOffsets>(data).ok())
+        else {
+            continue;
+        };
+        if let Some(signature_index) = offsets
+            .iter()
+            .position(|offsets| bytes_of(offsets) == bytes_of(&expected_offsets))
+        {
+            // There are at most `u8::MAX` signatures.
+            return Ok((index, signature_index as u8));
+        }
+    }
+    Err(Error::SignatureNotFound.into())
+}
+
+/// Checks that `account` is `expected_key` and has signed the transaction.
+fn verify_signer(account: &AccountInfo<'_>, expected_key: &Pubkey) -> ProgramResult {
+    if !account.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if account.key != expected_key {
+        return Err(Error::Unauthorized.into());
+    }
+    Ok(())
+}
+
+/// Checks that each of `data_accounts` is the data PDA of the price feed stored in it.
+/// Returns the accounts together with their price feed IDs.
+fn verify_data_accounts<'a, 'info>(
+    program_id: &Pubkey,
+    data_accounts: impl Iterator<Item = &'a AccountInfo<'info>>,
+) -> Result<Vec<(u32, &'a AccountInfo<'info>)>, ProgramError> {
+    data_accounts
+        .map(|data_account| {
+            let (price_feed, bump) = {
+                let state_data = data_account.data.borrow();
+                let state = State::load(&state_data)?;
+                (state.price_feed, state.bump)
+            };
+            // Cheaper than `find_data_pda` because the bump seed is known.
+            let data_pda_key = Pubkey::create_program_address(
+                &[DATA_PDA_SEED, &price_feed.to_le_bytes(), &[bump]],
+                program_id,
+            )
+            .map_err(|_| Error::InvalidPda)?;
+            if data_account.key != &data_pda_key {
+                return Err(Error::InvalidPda.into());
+            }
+            Ok((price_feed, data_account))
+        })
+        .collect()
+}
+
+/// Checks that each of `history_accounts` is the history PDA of the price feed stored in it.
+/// Returns the accounts together with their price feed IDs.
+fn verify_history_accounts<'a, 'info>(
+    program_id: &Pubkey,
+    history_accounts: impl Iterator<Item = &'a AccountInfo<'info>>,
+) -> Result<Vec<(u32, &'a AccountInfo<'info>)>, ProgramError> {
+    history_accounts
+        .map(|history_account| {
+            let (price_feed, bump) = {
+                let history_data = history_account.data.borrow();
+                let history = History::load(&history_data)?;
+                (history.price_feed, history.bump)
+            };
+            let history_pda_key = Pubkey::create_program_address(
+                &[HISTORY_PDA_SEED, &price_feed.to_le_bytes(), &[bump]],
+                program_id,
+            )
+            .map_err(|_| Error::InvalidPda)?;
+            if history_account.key != &history_pda_key {
+                return Err(Error::InvalidPda.into());
+            }
+            Ok((price_feed, history_account))
+        })
+        .collect()
+}
+
+/// Checks that each of `allowlist_accounts` is the updater allowlist PDA of the price feed
+/// stored in it. Returns the accounts together with their price feed IDs.
+fn verify_updater_allowlist_accounts<'a, 'info>(
+    program_id: &Pubkey,
+    allowlist_accounts: impl Iterator<Item = &'a AccountInfo<'info>>,
+) -> Result<Vec<(u32, &'a AccountInfo<'info>)>, ProgramError> {
+    allowlist_accounts
+        .map(|allowlist_account| {
+            let (price_feed, bump) = {
+                let allowlist_data = allowlist_account.data.borrow();
+                let allowlist = UpdaterAllowlist::load(&allowlist_data)?;
+                (allowlist.price_feed, allowlist.bump)
+            };
+            let allowlist_pda_key = Pubkey::create_program_address(
+                &[
+                    UPDATER_ALLOWLIST_PDA_SEED,
+                    &price_feed.to_le_bytes(),
+                    &[bump],
+                ],
+                program_id,
+            )
+            .map_err(|_| Error::InvalidPda)?;
+            if allowlist_account.key != &allowlist_pda_key {
+                return Err(Error::InvalidPda.into());
+            }
+            Ok((price_feed, allowlist_account))
+        })
+        .collect()
+}
+
+/// Checks that `updater_account` signed the update and is allowed to update each of
+/// `data_accounts` that has its updater allowlist enabled.
+fn verify_updater(
+    updater_account: &AccountInfo<'_>,
+    data_accounts: &[(u32, &AccountInfo<'_>)],
+    allowlist_accounts: &[(u32, &AccountInfo<'_>)],
+) -> ProgramResult {
+    for (price_feed, data_account) in data_accounts {
+        if State::load(&data_account.data.borrow())?.updater_allowlist_enabled == 0 {
+            continue;
+        }
+        let (_, allowlist_account) = allowlist_accounts
+            .iter()
+            .find(|(allowlist_price_feed, _)| allowlist_price_feed == price_feed)
+            .ok_or(Error::UnauthorizedUpdater)?;
+        if !updater_account.is_signer
+            || !UpdaterAllowlist::load(&allowlist_account.data.borrow())?
+                .updaters()
+                .contains(updater_account.key)
+        {
+            return Err(Error::UnauthorizedUpdater.into());
+        }
+    }
+    Ok(())
+}
+
+/// Data and history accounts passed to an update instruction, with their price feed IDs.
+type UpdateAccounts<'a, 'info> = (
+    Vec<(u32, &'a AccountInfo<'info>)>,
+    Vec<(u32, &'a AccountInfo<'info>)>,
+);
+
+/// Splits the accounts of an update instruction into data accounts, history accounts
+/// and updater allowlist accounts and verifies them. Each history account must belong
+/// to a price feed whose data account is also passed. `payer_account` must be allowed
+/// by the updater allowlists of the price feeds that have them enabled.
+/// None of the price feeds may be paused.
+fn verify_update_accounts<'a, 'info>(
+    program_id: &Pubkey,
+    payer_account: &AccountInfo<'info>,
+    data_account: &'a AccountInfo<'info>,
+    extra_accounts: &'a [AccountInfo<'info>],
+) -> Result<UpdateAccounts<'a, 'info>, ProgramError> {
+    let has_discriminator = |account: &AccountInfo, discriminator: &[u8]| {
+        account.owner == program_id && account.data.borrow().starts_with(discriminator)
+    };
+    let is_history_account =
+        |account: &AccountInfo| has_discriminator(account, &HISTORY_DISCRIMINATOR);
+    let is_allowlist_account =
+        |account: &AccountInfo| has_discriminator(account, &UPDATER_ALLOWLIST_DISCRIMINATOR);
+    let data_accounts = verify_data_accounts(
+        program_id,
+        iter::once(data_account).chain(
+            extra_accounts
+                .iter()
+                .filter(|account| !is_history_account(account) && !is_allowlist_account(account)),
+        ),
     )?;
+    for (_, data_account) in &data_accounts {
+        if State::load(&data_account.data.borrow())?.paused != 0 {
+            return Err(Error::Paused.into());
+        }
+    }
+    let history_accounts = verify_history_accounts(
+        program_id,
+        extra_accounts
+            .iter()
+            .filter(|account| is_history_account(account)),
+    )?;
+    for (history_price_feed, _) in &history_accounts {
+        if !data_accounts
+            .iter()
+            .any(|(price_feed, _)| price_feed == history_price_feed)
+        {
+            return Err(Error::WrongPriceFeed.into());
+        }
+    }
+    let allowlist_accounts = verify_updater_allowlist_accounts(
+        program_id,
+        extra_accounts
+            .iter()
+            .filter(|account| is_allowlist_account(account)),
+    )?;
+    verify_updater(payer_account, &data_accounts, &allowlist_accounts)?;
+    Ok((data_accounts, history_accounts))
+}
 
-    let pyth_message = LeEcdsaMessage::deserialize_slice(pyth_message)
-        .map_err(|_| ProgramError::InvalidInstructionData)?;
+/// Returns the cluster time in microseconds.
+fn now_us() -> Result<u64, ProgramError> {
+    // `Clock::unix_timestamp` is in seconds while the payload uses microseconds.
+    Ok(u64::try_from(Clock::get()?.unix_timestamp)
+        .unwrap_or(0)
+        .saturating_mul(1_000_000))
+}
 
-    // Deserialize and use the payload.
-    let data = PayloadData::deserialize_slice_le(&pyth_message.payload)
-        .map_err(|_| ProgramError::InvalidInstructionData)?;
+/// Applies each feed of the updates to the data account tracking that feed and appends
+/// the new price to the history account of that feed, if it was passed.
+/// Every data account must receive an update.
+fn apply_update(
+    data_accounts: &[(u32, &AccountInfo<'_>)],
+    history_accounts: &[(u32, &AccountInfo<'_>)],
+    payloads: &[PayloadData],
+    update_args: &UpdateArgs,
+    verification_path: VerificationPath,
+) -> ProgramResult {
+    let expected_price_feed_id = update_args.expected_price_feed_id;
+    if expected_price_feed_id != 0
+        && !payloads
+            .iter()
+            .flat_map(|data| &data.feeds)
+            .any(|feed| feed.feed_id.0 == expected_price_feed_id)
+    {
+        return Err(Error::WrongPriceFeed.into());
+    }
+    let unmatched_feed_mode = UnmatchedFeedMode::from_u8(update_args.unmatched_feed_mode)
+        .ok_or(ProgramError::InvalidInstructionData)?;
+
+    let now_us = now_us()?;
+    let mut is_updated = vec![false; data_accounts.len()];
+    for data in payloads {
+        if data.timestamp_us.0 > now_us.saturating_add(MAX_FUTURE_SKEW_US) {
+            return Err(Error::TimestampInFuture.into());
+        }
+        // Saturating so that a timestamp slightly ahead of the cluster clock reads as age 0.
+        let age_us = now_us.saturating_sub(data.timestamp_us.0);
 
-    apply_update(data_account, &data)
+        // Check the constraints of the caller.
+        let max_age_us = update_args.max_age_us;
+        if data.timestamp_us.0 < update_args.min_timestamp_us
+            || (max_age_us != 0 && age_us > max_age_us)
+        {
+            return Err(Error::PriceStale.into());
+        }
+
+        for feed in &data.feeds {
+            let Some(index) = data_accounts
+                .iter()
+                .position(|(price_feed, _)| *price_feed == feed.feed_id.0)
+            else {
+                match unmatched_feed_mode {
+                    UnmatchedFeedMode::Reject => return Err(Error::WrongPriceFeed.into()),
+                    UnmatchedFeedMode::Skip => continue,
+                }
+            };
+            let (price_feed, data_account) = data_accounts[index];
+            let is_applied = apply_feed_update(data_account, data, age_us, feed)?;
+            is_updated[index] = true;
+            if !is_applied {
+                continue;
+            }
+
+            let state_data = data_account.data.borrow();
+            let state = State::load(&state_data)?;
+            sol_log_data(&[
+                &PRICE_UPDATE_EVENT_DISCRIMINATOR,
+                bytes_of(&PriceUpdateEvent {
+                    price_feed_id: price_feed,
+                    price: state.latest_price,
+                    exponent: state.latest_exponent,
+                    timestamp_us: state.latest_timestamp,
+                    channel_id: data.channel_id.0,
+                    verification_path: verification_path as u8,
+                }),
+            ]);
+            if let Some((_, history_account)) = history_accounts
+                .iter()
+                .find(|(history_price_feed, _)| *history_price_feed == price_feed)
+            {
+                let mut history_data = history_account.data.borrow_mut();
+                History::load_mut(*history_data)?.push(HistoryEntry {
+                    timestamp_us: state.latest_timestamp,
+                    price: state.latest_price,
+                    exponent: state.latest_exponent,
+                });
+            }
+        }
+    }
+    if is_updated.contains(&false) {
+        return Err(Error::WrongPriceFeed.into());
+    }
+    Ok(())
 }
 
-fn apply_update(data_account: &AccountInfo<'_>, data: &PayloadData) -> ProgramResult {
+/// Applies a feed of the update to its data account. Returns `false` if the update
+/// was quarantined instead.
+fn apply_feed_update(
+    data_account: &AccountInfo<'_>,
+    data: &PayloadData,
+    age_us: u64,
+    feed: &PayloadFeedData,
+) -> Result<bool, ProgramError> {
     // Read the data PDA of our example contract.
     let mut state_data = data_account.data.borrow_mut();
-    let state =
-        try_from_bytes_mut::<State>(*state_data).map_err(|_| ProgramError::InvalidAccountData)?;
+    let state = State::load_mut(*state_data)?;
 
-    if data.feeds.is_empty() || data.feeds[0].properties.is_empty() {
-        return Err(ProgramError::InvalidInstructionData);
+    // Properties may come in any order, depending on the subscription.
+    let mut price = None;
+    let mut exponent = None;
+    let mut best_bid_price = None;
+    let mut best_ask_price = None;
+    let mut confidence = None;
+    let mut publisher_count = None;
+    for property in &feed.properties {
+        match property {
+            PayloadPropertyValue::Price(value) => price = *value,
+            PayloadPropertyValue::BestBidPrice(value) => best_bid_price = *value,
+            PayloadPropertyValue::BestAskPrice(value) => best_ask_price = *value,
+            PayloadPropertyValue::PublisherCount(value) => publisher_count = Some(*value),
+            PayloadPropertyValue::Exponent(value) => exponent = Some(*value),
+            PayloadPropertyValue::Confidence(value) => confidence = *value,
+            // Funding properties are not relevant to this contract.
+            PayloadPropertyValue::FundingRate(_) | PayloadPropertyValue::FundingTimestamp(_) => {}
+        }
     }
-    if state.price_feed != data.feeds[0].feed_id.0 {
-        return Err(ProgramError::InvalidInstructionData);
+    let price = price.map(|price| price.into_inner().get());
+    let is_applied = state.check_update(data, age_us, price)?;
+    let price = price.ok_or(Error::MissingPrice)?;
+    if !is_applied {
+        state.pending_timestamp = data.timestamp_us.0;
+        state.pending_price = price;
+        return Ok(false);
     }
-    let expected_channel = Channel::FixedRate(FixedRate::from_ms(1).unwrap());
-    if data.channel_id != expected_channel.id() {
-        return Err(ProgramError::InvalidInstructionData);
+    state.pending_timestamp = 0;
+    state.pending_price = 0;
+
+    let mut present_properties = 0;
+    state.latest_price = price;
+    state.latest_timestamp = data.timestamp_us.0;
+    state.latest_exponent =
+        present_or_default(exponent, property_flags::EXPONENT, &mut present_properties);
+    state.latest_best_bid_price = present_or_default(
+        best_bid_price.map(|price| price.into_inner().get()),
+        property_flags::BEST_BID_PRICE,
+        &mut present_properties,
+    );
+    state.latest_best_ask_price = present_or_default(
+        best_ask_price.map(|price| price.into_inner().get()),
+        property_flags::BEST_ASK_PRICE,
+        &mut present_properties,
+    );
+    state.latest_confidence = present_or_default(
+        confidence.map(|confidence| confidence.into_inner().get()),
+        property_flags::CONFIDENCE,
+        &mut present_properties,
+    );
+    state.latest_publisher_count = present_or_default(
+        publisher_count,
+        property_flags::PUBLISHER_COUNT,
+        &mut present_properties,
+    );
+    state.present_properties = present_properties;
+    Ok(true)
+}
+
+/// Checks that the move from the `previous` to the `next` price (given as timestamp and price)
+/// is at most `max_bps_per_second` basis points of the previous price per second elapsed
+/// between them. A limit of 0 disables the check.
+fn is_within_deviation(max_bps_per_second: u32, previous: (u64, i64), next: (u64, i64)) -> bool {
+    if max_bps_per_second == 0 {
+        return true;
     }
-    if data.timestamp_us.0 <= state.latest_timestamp {
-        return Err(ProgramError::AccountAlreadyInitialized);
+    let elapsed_us = next.0.saturating_sub(previous.0);
+    let change = (i128::from(next.1) - i128::from(previous.1)).unsigned_abs();
+    // change / |previous price| * 10_000 <= max_bps_per_second * elapsed_us / 1_000_000
+    change * 10_000 * 1_000_000
+        <= u128::from(max_bps_per_second)
+            .saturating_mul(elapsed_us.into())
+            .saturating_mul(previous.1.unsigned_abs().into())
+}
+
+/// Returns the value to store for an optional property, recording its presence
+/// in `present_properties`.
+fn present_or_default<T: Default>(value: Option<T>, flag: u8, present_properties: &mut u8) -> T {
+    if value.is_some() {
+        *present_properties |= flag;
     }
-    let PayloadPropertyValue::Price(Some(price)) = data.feeds[0].properties[0] else {
-        return Err(ProgramError::InvalidInstructionData);
-    };
-    state.latest_price = price.into_inner().into();
-    state.latest_timestamp = data.timestamp_us.0;
-    Ok(())
+    value.unwrap_or_default()
 }
@@ -1,22 +1,82 @@
 use {
     anchor_lang::InstructionData,
-    bytemuck::{bytes_of, from_bytes},
-    pyth_lazer_solana_contract::ed25519_program_args,
+    bytemuck::{bytes_of, from_bytes, Zeroable},
+    byteorder::LE,
+    pyth_lazer_solana_contract::{
+        ed25519_program_args,
+        protocol::{
+            message::SolanaMessage,
+            payload::{PayloadData, PayloadFeedData, PayloadPropertyValue},
+            router::{Channel, FixedRate, Price, PriceFeedId, TimestampUs},
+        },
+    },
     pyth_lazer_solana_example::{
-        process_instruction, InitializeArgs, Instruction as ExampleInstruction, State, UpdateArgs,
+        channel_bit, cpi, events::parse_price_update_events, find_data_pda, find_history_pda,
+        find_updater_allowlist_pda, instructions, process_instruction, DeviationMode, Error,
+        InitializeArgs, PriceResult, PriceUpdateEvent, SetConfigArgs, State, TwapResult,
+        UnmatchedFeedMode, UpdateArgs, VerificationPath, DISCOVER_ED25519_INSTRUCTION,
+        HISTORY_CAPACITY, MAX_FUTURE_SKEW_US,
+    },
+    solana_program::{
+        account_info::AccountInfo,
+        entrypoint::ProgramResult,
+        instruction::{AccountMeta, Instruction},
+        program::set_return_data,
+        program_error::ProgramError,
     },
-    solana_program::instruction::{AccountMeta, Instruction},
-    solana_program_test::{processor, ProgramTest},
+    solana_program_test::{processor, BanksClientError, ProgramTest, ProgramTestContext},
     solana_sdk::{
-        pubkey::Pubkey, signer::Signer, system_instruction, system_program, sysvar,
-        transaction::Transaction,
+        clock::Clock,
+        compute_budget::ComputeBudgetInstruction,
+        instruction::InstructionError,
+        pubkey::Pubkey,
+        signature::Keypair,
+        signer::Signer,
+        system_instruction, system_program,
+        transaction::{Transaction, TransactionError},
     },
     std::env,
     std::mem::size_of,
 };
 
-#[tokio::test]
-async fn test1() {
+/// Maximum age of updates accepted by the price feeds initialized in tests.
+const MAX_AGE_US: u64 = 10_000_000;
+
+/// Update of price feed 2 in the LE-ECDSA format signed by `ECDSA_SIGNER_HEX`,
+/// with timestamp 1740480250860000 and price 11488100000000.
+const ECDSA_UPDATE_HEX: &str = "e4bd474df2e5eaee8d9f99ee08f63f4268efa1ff89360767dfcd7677822891494ed32a5300d86440270aeabe9617c1924caad9486842c93bab6eaa7947cbea1a8599b4be011c0075d3c793e0511723f52e0600010102000000010000a11ec8720a0000";
+
+/// Ethereum address of the signer of `ECDSA_UPDATE_HEX`.
+const ECDSA_SIGNER_HEX: &str = "b8d50f0bae75bf6e03c104903d7c3afc4a6596da";
+
+/// Program that reads prices of the example program via CPI.
+const PRICE_CONSUMER_ID: Pubkey = Pubkey::new_from_array([7; 32]);
+
+/// Reads the price with `cpi::get_price` and returns it as its own return data.
+/// Data: maximum age of the price (u64)
+/// Accounts:
+/// 1. example program
+/// 2. example data account of the price feed
+fn process_price_consumer_instruction(
+    _program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    data: &[u8],
+) -> ProgramResult {
+    let max_age_us = u64::from_le_bytes(data.try_into().unwrap());
+    let price = cpi::get_price(&accounts[0], &accounts[1], max_age_us)?;
+    set_return_data(bytes_of(&price));
+    Ok(())
+}
+
+struct TestEnv {
+    context: ProgramTestContext,
+    treasury: Pubkey,
+    /// Trusted signer of the Pyth Lazer contract that can be used to sign arbitrary updates.
+    test_signer: Keypair,
+}
+
+/// Starts the test validator with both programs and initializes the Pyth Lazer contract.
+async fn setup() -> TestEnv {
     if env::var("SBF_OUT_DIR").is_err() {
         env::set_var(
             "SBF_OUT_DIR",
@@ -37,7 +97,17 @@ async fn test1() {
         pyth_lazer_solana_contract::ID,
         None,
     );
-    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    // The consumer only exists as a native program.
+    program_test.prefer_bpf(false);
+    program_test.add_program(
+        "price_consumer",
+        PRICE_CONSUMER_ID,
+        processor!(process_price_consumer_instruction),
+    );
+    let context = program_test.start_with_context().await;
+    let mut banks_client = context.banks_client.clone();
+    let payer = &context.payer;
+    let recent_blockhash = context.last_blockhash;
 
     let treasury =
         Pubkey::create_with_seed(&payer.pubkey(), "treasury", &system_program::ID).unwrap();
@@ -54,7 +124,7 @@ async fn test1() {
         )],
         Some(&payer.pubkey()),
     );
-    transaction_create_treasury.sign(&[&payer], recent_blockhash);
+    transaction_create_treasury.sign(&[payer], recent_blockhash);
     banks_client
         .process_transaction(transaction_create_treasury)
         .await
@@ -76,7 +146,7 @@ async fn test1() {
         )],
         Some(&payer.pubkey()),
     );
-    transaction_init_contract.sign(&[&payer], recent_blockhash);
+    transaction_init_contract.sign(&[payer], recent_blockhash);
     banks_client
         .process_transaction(transaction_init_contract)
         .await
@@ -84,13 +154,6 @@ async fn test1() {
 
     let verifying_key =
         hex::decode("74313a6525edf99936aa1477e94c72bc5cc617b21745f5f03296f3154461f214").unwrap();
-    let message = hex::decode(
-        "b9011a82e5cddee2c1bd364c8c57e1c98a6a28d194afcad410ff412226c8b2ae931ff59a57147cb47c7307\
-        afc2a0a1abec4dd7e835a5b7113cf5aeac13a745c6bed6c60074313a6525edf99936aa1477e94c72bc5cc61\
-        7b21745f5f03296f3154461f2141c0075d3c7931c9773f30a240600010102000000010000e1f50500000000",
-    )
-    .unwrap();
-
     let mut transaction_set_trusted = Transaction::new_with_payer(
         &[Instruction::new_with_bytes(
             pyth_lazer_solana_contract::ID,
@@ -106,82 +169,331 @@ async fn test1() {
         )],
         Some(&payer.pubkey()),
     );
-    transaction_set_trusted.sign(&[&payer], recent_blockhash);
+    transaction_set_trusted.sign(&[payer], recent_blockhash);
     banks_client
         .process_transaction(transaction_set_trusted)
         .await
         .unwrap();
 
-    let (data_pda_key, _) =
-        Pubkey::find_program_address(&[b"data"], &pyth_lazer_solana_example::ID);
-
-    let mut init_data = vec![ExampleInstruction::Initialize as u8];
-    init_data.extend_from_slice(bytes_of(&InitializeArgs { price_feed_id: 2 }));
-
-    let mut transaction_init = Transaction::new_with_payer(
+    let test_signer = Keypair::new();
+    let mut transaction_set_test_trusted = Transaction::new_with_payer(
         &[Instruction::new_with_bytes(
-            pyth_lazer_solana_example::ID,
-            &init_data,
+            pyth_lazer_solana_contract::ID,
+            &pyth_lazer_solana_contract::instruction::Update {
+                trusted_signer: test_signer.pubkey(),
+                expires_at: i64::MAX,
+            }
+            .data(),
             vec![
                 AccountMeta::new(payer.pubkey(), true),
-                AccountMeta::new(data_pda_key, false),
-                AccountMeta::new_readonly(system_program::ID, false),
+                AccountMeta::new(pyth_lazer_solana_contract::STORAGE_ID, false),
             ],
         )],
         Some(&payer.pubkey()),
     );
-    transaction_init.sign(&[&payer], recent_blockhash);
+    transaction_set_test_trusted.sign(&[payer], recent_blockhash);
     banks_client
-        .process_transaction(transaction_init)
+        .process_transaction(transaction_set_test_trusted)
         .await
         .unwrap();
 
-    let state = banks_client
-        .get_account(data_pda_key)
+    TestEnv {
+        context,
+        treasury,
+        test_signer,
+    }
+}
+
+impl TestEnv {
+    async fn initialize_feed(&mut self, price_feed_id: u32) {
+        self.send(&[instructions::initialize(
+            &pyth_lazer_solana_example::ID,
+            &self.context.payer.pubkey(),
+            &InitializeArgs {
+                price_feed_id,
+                max_age_us: MAX_AGE_US,
+                accepted_channels: channel_bit(Channel::FixedRate(FixedRate::MIN).id()),
+                max_deviation_bps_per_second: 0,
+                deviation_mode: DeviationMode::Reject as u8,
+                authority: self.context.payer.pubkey(),
+            },
+        )])
         .await
-        .unwrap()
         .unwrap();
-    let state = from_bytes::<State>(&state.data);
-    assert_eq!({ state.price_feed }, 2);
-    assert_eq!({ state.latest_price }, 0);
-    assert_eq!({ state.latest_timestamp }, 0);
+    }
+
+    async fn initialize_history(&mut self, price_feed_id: u32) {
+        self.send(&[instructions::initialize_history(
+            &pyth_lazer_solana_example::ID,
+            &self.context.payer.pubkey(),
+            price_feed_id,
+        )])
+        .await
+        .unwrap();
+    }
 
-    let mut update_data = vec![ExampleInstruction::Update as u8];
-    update_data.extend_from_slice(bytes_of(&UpdateArgs { hello: 42 }));
-    update_data.extend_from_slice(&message);
+    async fn send(&mut self, instructions: &[Instruction]) -> Result<(), BanksClientError> {
+        self.send_signed(instructions, &[]).await
+    }
 
-    // Instruction #0 will be ed25519 instruction;
-    // Instruction #1 will be our contract instruction.
-    let instruction_index = 1;
-    // Total offset of Pyth Lazer update within the instruction data;
-    // 1 byte is the instruction type.
-    let message_offset = (size_of::<UpdateArgs>() + 1).try_into().unwrap();
-    let ed25519_args = pyth_lazer_solana_contract::Ed25519SignatureOffsets::new(
-        &message,
-        instruction_index,
-        message_offset,
+    /// Sends a transaction signed by the payer and `signers`.
+    async fn send_signed(
+        &mut self,
+        instructions: &[Instruction],
+        signers: &[&Keypair],
+    ) -> Result<(), BanksClientError> {
+        let mut transaction =
+            Transaction::new_with_payer(instructions, Some(&self.context.payer.pubkey()));
+        let mut all_signers = vec![&self.context.payer];
+        all_signers.extend_from_slice(signers);
+        transaction.sign(&all_signers, self.context.last_blockhash);
+        self.context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+    }
+
+    /// Adds the signer of `ECDSA_UPDATE_HEX` to the trusted signers of the Pyth Lazer contract.
+    async fn set_ecdsa_signer(&mut self) {
+        self.send(&[Instruction::new_with_bytes(
+            pyth_lazer_solana_contract::ID,
+            &pyth_lazer_solana_contract::instruction::UpdateEcdsaSigner {
+                trusted_signer: hex::decode(ECDSA_SIGNER_HEX).unwrap().try_into().unwrap(),
+                expires_at: i64::MAX,
+            }
+            .data(),
+            vec![
+                AccountMeta::new(self.context.payer.pubkey(), true),
+                AccountMeta::new(pyth_lazer_solana_contract::STORAGE_ID, false),
+            ],
+        )])
+        .await
+        .unwrap();
+    }
+
+    /// Simulates a transaction and returns the compute units it consumed.
+    async fn compute_units(&mut self, instructions: &[Instruction]) -> u64 {
+        // Benchmarks run long enough for the blockhash to expire.
+        self.context.last_blockhash = self.context.get_new_latest_blockhash().await.unwrap();
+        let mut transaction =
+            Transaction::new_with_payer(instructions, Some(&self.context.payer.pubkey()));
+        transaction.sign(&[&self.context.payer], self.context.last_blockhash);
+        let simulation = self
+            .context
+            .banks_client
+            .simulate_transaction(transaction)
+            .await
+            .unwrap();
+        simulation.result.unwrap().unwrap();
+        simulation.simulation_details.unwrap().units_consumed
+    }
+
+    /// Sends a transaction signed by the payer and returns its logs.
+    async fn send_with_logs(&mut self, instructions: &[Instruction]) -> Vec<String> {
+        let mut transaction =
+            Transaction::new_with_payer(instructions, Some(&self.context.payer.pubkey()));
+        transaction.sign(&[&self.context.payer], self.context.last_blockhash);
+        let result = self
+            .context
+            .banks_client
+            .process_transaction_with_metadata(transaction)
+            .await
+            .unwrap();
+        result.result.unwrap();
+        result.metadata.unwrap().log_messages
+    }
+
+    /// Simulates a transaction and returns the data returned by its last instruction.
+    async fn simulate(
+        &mut self,
+        instructions: &[Instruction],
+    ) -> Result<Vec<u8>, TransactionError> {
+        let mut transaction =
+            Transaction::new_with_payer(instructions, Some(&self.context.payer.pubkey()));
+        transaction.sign(&[&self.context.payer], self.context.last_blockhash);
+        let simulation = self
+            .context
+            .banks_client
+            .simulate_transaction(transaction)
+            .await
+            .unwrap();
+        simulation.result.unwrap()?;
+        Ok(simulation
+            .simulation_details
+            .unwrap()
+            .return_data
+            .map(|return_data| return_data.data)
+            .unwrap_or_default())
+    }
+
+    /// Sets the cluster clock to `unix_timestamp` (in seconds).
+    async fn set_clock(&mut self, unix_timestamp: i64) {
+        let mut clock: Clock = self.context.banks_client.get_sysvar().await.unwrap();
+        clock.unix_timestamp = unix_timestamp;
+        self.context.set_sysvar(&clock);
+    }
+
+    async fn state(&mut self, price_feed_id: u32) -> State {
+        let account = self
+            .context
+            .banks_client
+            .get_account(find_data_pda(&pyth_lazer_solana_example::ID, price_feed_id).0)
+            .await
+            .unwrap()
+            .unwrap();
+        *from_bytes::<State>(&account.data)
+    }
+
+    /// Returns the ed25519 instruction and the `Update` instruction applying `message`
+    /// to the data accounts of `price_feed_ids`.
+    fn update_instructions(
+        &self,
+        message: &[u8],
+        unmatched_feed_mode: UnmatchedFeedMode,
+        price_feed_ids: &[u32],
+    ) -> Vec<Instruction> {
+        self.update_instructions_with_args(
+            message,
+            &UpdateArgs::new(unmatched_feed_mode),
+            price_feed_ids,
+        )
+    }
+
+    /// Same as `update_instructions`, but with custom `UpdateArgs`.
+    fn update_instructions_with_args(
+        &self,
+        message: &[u8],
+        update_args: &UpdateArgs,
+        price_feed_ids: &[u32],
+    ) -> Vec<Instruction> {
+        instructions::update(
+            &pyth_lazer_solana_example::ID,
+            &self.context.payer.pubkey(),
+            &self.treasury,
+            message,
+            update_args,
+            price_feed_ids,
+        )
+    }
+}
+
+/// Asserts that the example program instruction at `index` failed with `error`.
+fn assert_error(result: Result<(), BanksClientError>, index: u8, error: Error) {
+    assert_eq!(
+        result.unwrap_err().unwrap(),
+        TransactionError::InstructionError(index, InstructionError::Custom(error as u32)),
     );
+}
+
+/// Creates a Pyth Lazer update in the Solana format signed by `signer`.
+fn sign_update(signer: &Keypair, payload: &PayloadData) -> Vec<u8> {
+    let mut payload_data = Vec::new();
+    payload.serialize::<LE>(&mut payload_data).unwrap();
+    let message = SolanaMessage {
+        signature: signer
+            .sign_message(&payload_data)
+            .as_ref()
+            .try_into()
+            .unwrap(),
+        public_key: signer.pubkey().to_bytes(),
+        payload: payload_data,
+    };
+    let mut data = Vec::new();
+    message.serialize(&mut data).unwrap();
+    data
+}
+
+/// Creates a payload of the 1 ms fixed-rate channel, the only channel accepted by the price
+/// feeds of `TestEnv::initialize_feed`, containing only the price property of each feed.
+fn price_payload(timestamp_us: u64, prices: &[(u32, i64)]) -> PayloadData {
+    channel_price_payload(Channel::FixedRate(FixedRate::MIN), timestamp_us, prices)
+}
+
+/// Creates a payload containing only the price property of each feed.
+fn channel_price_payload(
+    channel: Channel,
+    timestamp_us: u64,
+    prices: &[(u32, i64)],
+) -> PayloadData {
+    PayloadData {
+        timestamp_us: TimestampUs(timestamp_us),
+        channel_id: channel.id(),
+        feeds: prices
+            .iter()
+            .map(|(price_feed_id, price)| PayloadFeedData {
+                feed_id: PriceFeedId(*price_feed_id),
+                properties: vec![PayloadPropertyValue::Price(Some(
+                    Price::new(*price).unwrap(),
+                ))],
+            })
+            .collect(),
+    }
+}
+
+#[tokio::test]
+async fn test1() {
+    let mut env = setup().await;
+    let mut banks_client = env.context.banks_client.clone();
+    let payer = env.context.payer.insecure_clone();
+    let recent_blockhash = env.context.last_blockhash;
+    let treasury = env.treasury;
+
+    let message = hex::decode(
+        "b9011a82e5cddee2c1bd364c8c57e1c98a6a28d194afcad410ff412226c8b2ae931ff59a57147cb47c7307\
+        afc2a0a1abec4dd7e835a5b7113cf5aeac13a745c6bed6c60074313a6525edf99936aa1477e94c72bc5cc61\
+        7b21745f5f03296f3154461f2141c0075d3c7931c9773f30a240600010102000000010000e1f50500000000",
+    )
+    .unwrap();
+
+    let (data_pda_key, _) = find_data_pda(&pyth_lazer_solana_example::ID, 2);
+    let (other_data_pda_key, _) = find_data_pda(&pyth_lazer_solana_example::ID, 3);
+    assert_ne!(data_pda_key, other_data_pda_key);
+
+    // Each price feed gets its own data PDA.
+    for (price_feed_id, data_pda_key) in [(2, data_pda_key), (3, other_data_pda_key)] {
+        let mut transaction_init = Transaction::new_with_payer(
+            &[instructions::initialize(
+                &pyth_lazer_solana_example::ID,
+                &payer.pubkey(),
+                &InitializeArgs {
+                    price_feed_id,
+                    max_age_us: MAX_AGE_US,
+                    accepted_channels: channel_bit(Channel::FixedRate(FixedRate::MIN).id()),
+                    max_deviation_bps_per_second: 0,
+                    deviation_mode: DeviationMode::Reject as u8,
+                    authority: payer.pubkey(),
+                },
+            )],
+            Some(&payer.pubkey()),
+        );
+        transaction_init.sign(&[&payer], recent_blockhash);
+        banks_client
+            .process_transaction(transaction_init)
+            .await
+            .unwrap();
+
+        let state = banks_client
+            .get_account(data_pda_key)
+            .await
+            .unwrap()
+            .unwrap();
+        let state = from_bytes::<State>(&state.data);
+        assert_eq!({ state.price_feed }, price_feed_id);
+        assert_eq!({ state.latest_price }, 0);
+        assert_eq!({ state.latest_timestamp }, 0);
+    }
+
+    // The update must be recent according to the cluster clock.
+    env.set_clock(1728479313).await;
     let mut transaction_update = Transaction::new_with_payer(
-        &[
-            Instruction::new_with_bytes(
-                solana_program::ed25519_program::ID,
-                &ed25519_program_args(&[ed25519_args]),
-                vec![],
-            ),
-            Instruction::new_with_bytes(
-                pyth_lazer_solana_example::ID,
-                &update_data,
-                vec![
-                    AccountMeta::new(payer.pubkey(), true),
-                    AccountMeta::new(data_pda_key, false),
-                    AccountMeta::new(pyth_lazer_solana_contract::ID, false),
-                    AccountMeta::new_readonly(pyth_lazer_solana_contract::STORAGE_ID, false),
-                    AccountMeta::new(treasury, false),
-                    AccountMeta::new_readonly(system_program::ID, false),
-                    AccountMeta::new_readonly(sysvar::instructions::ID, false),
-                ],
-            ),
-        ],
+        &instructions::update(
+            &pyth_lazer_solana_example::ID,
+            &payer.pubkey(),
+            &treasury,
+            &message,
+            &UpdateArgs::new(UnmatchedFeedMode::Reject),
+            &[2],
+        ),
         Some(&payer.pubkey()),
     );
     transaction_update.sign(&[&payer], recent_blockhash);
@@ -199,12 +511,21 @@ async fn test1() {
     assert_eq!({ state.price_feed }, 2);
     assert_eq!({ state.latest_timestamp }, 1728479312975644);
     assert_eq!({ state.latest_price }, 100000000);
+    assert_eq!(state.exponent(), None);
 
-    let verifying_key_ecdsa = hex::decode("b8d50f0bae75bf6e03c104903d7c3afc4a6596da").unwrap();
-    let message_ecdsa = hex::decode(
-        "e4bd474df2e5eaee8d9f99ee08f63f4268efa1ff89360767dfcd7677822891494ed32a5300d86440270aeabe9617c1924caad9486842c93bab6eaa7947cbea1a8599b4be011c0075d3c793e0511723f52e0600010102000000010000a11ec8720a0000",
-    )
-    .unwrap();
+    // The data PDA of the other price feed is not affected.
+    let other_state = banks_client
+        .get_account(other_data_pda_key)
+        .await
+        .unwrap()
+        .unwrap();
+    let other_state = from_bytes::<State>(&other_state.data);
+    assert_eq!({ other_state.price_feed }, 3);
+    assert_eq!({ other_state.latest_timestamp }, 0);
+    assert_eq!({ other_state.latest_price }, 0);
+
+    let verifying_key_ecdsa = hex::decode(ECDSA_SIGNER_HEX).unwrap();
+    let message_ecdsa = hex::decode(ECDSA_UPDATE_HEX).unwrap();
 
     let mut transaction_set_ecdsa_trusted = Transaction::new_with_payer(
         &[Instruction::new_with_bytes(
@@ -227,23 +548,16 @@ async fn test1() {
         .await
         .unwrap();
 
-    let mut update_ecdsa_data = vec![ExampleInstruction::UpdateEcdsa as u8];
-    update_ecdsa_data.extend_from_slice(bytes_of(&UpdateArgs { hello: 42 }));
-    update_ecdsa_data.extend_from_slice(&message_ecdsa);
-
+    env.set_clock(1740480251).await;
     let mut transaction_update = Transaction::new_with_payer(
-        &[Instruction::new_with_bytes(
-            pyth_lazer_solana_example::ID,
-            &update_ecdsa_data,
-            vec![
-                AccountMeta::new(payer.pubkey(), true),
-                AccountMeta::new(data_pda_key, false),
-                AccountMeta::new(pyth_lazer_solana_contract::ID, false),
-                AccountMeta::new_readonly(pyth_lazer_solana_contract::STORAGE_ID, false),
-                AccountMeta::new(treasury, false),
-                AccountMeta::new_readonly(system_program::ID, false),
-            ],
-        )],
+        &instructions::update_ecdsa(
+            &pyth_lazer_solana_example::ID,
+            &payer.pubkey(),
+            &treasury,
+            &message_ecdsa,
+            &UpdateArgs::new(UnmatchedFeedMode::Reject),
+            &[2],
+        ),
         Some(&payer.pubkey()),
     );
     transaction_update.sign(&[&payer], recent_blockhash);
@@ -262,3 +576,1353 @@ async fn test1() {
     assert_eq!({ state.latest_timestamp }, 1740480250860000);
     assert_eq!({ state.latest_price }, 11488100000000);
 }
+
+#[tokio::test]
+async fn test_update_multiple_feeds() {
+    let mut env = setup().await;
+    env.set_clock(2).await;
+    for price_feed_id in [2, 3, 4] {
+        env.initialize_feed(price_feed_id).await;
+    }
+
+    // Feed 5 has no data account.
+    let message = sign_update(
+        &env.test_signer,
+        &price_payload(1_000_000, &[(2, 100), (3, 200), (5, 500)]),
+    );
+    let result = env
+        .send(&env.update_instructions(&message, UnmatchedFeedMode::Reject, &[2, 3]))
+        .await;
+    assert_error(result, 1, Error::WrongPriceFeed);
+    env.send(&env.update_instructions(&message, UnmatchedFeedMode::Skip, &[2, 3]))
+        .await
+        .unwrap();
+
+    let state = env.state(2).await;
+    assert_eq!({ state.latest_timestamp }, 1_000_000);
+    assert_eq!({ state.latest_price }, 100);
+    let state = env.state(3).await;
+    assert_eq!({ state.latest_timestamp }, 1_000_000);
+    assert_eq!({ state.latest_price }, 200);
+
+    // Every passed data account must be updated.
+    let message = sign_update(
+        &env.test_signer,
+        &price_payload(2_000_000, &[(2, 101), (3, 201)]),
+    );
+    let result = env
+        .send(&env.update_instructions(&message, UnmatchedFeedMode::Skip, &[2, 3, 4]))
+        .await;
+    assert_error(result, 1, Error::WrongPriceFeed);
+    env.send(&env.update_instructions(&message, UnmatchedFeedMode::Reject, &[3, 2]))
+        .await
+        .unwrap();
+
+    let state = env.state(2).await;
+    assert_eq!({ state.latest_timestamp }, 2_000_000);
+    assert_eq!({ state.latest_price }, 101);
+    let state = env.state(3).await;
+    assert_eq!({ state.latest_timestamp }, 2_000_000);
+    assert_eq!({ state.latest_price }, 201);
+    let state = env.state(4).await;
+    assert_eq!({ state.latest_timestamp }, 0);
+}
+
+#[tokio::test]
+async fn test_update_all_properties() {
+    let mut env = setup().await;
+    env.set_clock(3).await;
+    env.initialize_feed(2).await;
+
+    let price = |value| Some(Price::new(value).unwrap());
+    let payload = |timestamp_us, properties| PayloadData {
+        timestamp_us: TimestampUs(timestamp_us),
+        channel_id: Channel::FixedRate(FixedRate::MIN).id(),
+        feeds: vec![PayloadFeedData {
+            feed_id: PriceFeedId(2),
+            properties,
+        }],
+    };
+
+    // Properties are not required to be in any particular order.
+    let message = sign_update(
+        &env.test_signer,
+        &payload(
+            1_000_000,
+            vec![
+                PayloadPropertyValue::Exponent(-8),
+                PayloadPropertyValue::Confidence(price(5)),
+                PayloadPropertyValue::BestAskPrice(None),
+                PayloadPropertyValue::Price(price(100)),
+                PayloadPropertyValue::FundingRate(None),
+                PayloadPropertyValue::PublisherCount(7),
+                PayloadPropertyValue::BestBidPrice(price(99)),
+            ],
+        ),
+    );
+    env.send(&env.update_instructions(&message, UnmatchedFeedMode::Reject, &[2]))
+        .await
+        .unwrap();
+
+    let state = env.state(2).await;
+    assert_eq!({ state.latest_price }, 100);
+    assert_eq!(state.exponent(), Some(-8));
+    assert_eq!(state.confidence(), Some(5));
+    assert_eq!(state.best_bid_price(), Some(99));
+    assert_eq!(state.best_ask_price(), None);
+    assert_eq!(state.publisher_count(), Some(7));
+
+    // Properties missing from the next update are no longer present.
+    let message = sign_update(
+        &env.test_signer,
+        &payload(
+            2_000_000,
+            vec![
+                PayloadPropertyValue::BestAskPrice(price(102)),
+                PayloadPropertyValue::Price(price(101)),
+            ],
+        ),
+    );
+    env.send(&env.update_instructions(&message, UnmatchedFeedMode::Reject, &[2]))
+        .await
+        .unwrap();
+
+    let state = env.state(2).await;
+    assert_eq!({ state.latest_price }, 101);
+    assert_eq!(state.exponent(), None);
+    assert_eq!(state.confidence(), None);
+    assert_eq!(state.best_bid_price(), None);
+    assert_eq!(state.best_ask_price(), Some(102));
+    assert_eq!(state.publisher_count(), None);
+
+    // The price itself is required.
+    let message = sign_update(
+        &env.test_signer,
+        &payload(
+            3_000_000,
+            vec![
+                PayloadPropertyValue::Exponent(-8),
+                PayloadPropertyValue::Price(None),
+            ],
+        ),
+    );
+    let result = env
+        .send(&env.update_instructions(&message, UnmatchedFeedMode::Reject, &[2]))
+        .await;
+    assert_error(result, 1, Error::MissingPrice);
+}
+
+#[tokio::test]
+async fn test_update_staleness() {
+    let mut env = setup().await;
+    env.initialize_feed(2).await;
+    env.set_clock(100).await;
+    let now_us = 100_000_000;
+
+    let message = sign_update(
+        &env.test_signer,
+        &price_payload(now_us - MAX_AGE_US - 1, &[(2, 100)]),
+    );
+    let result = env
+        .send(&env.update_instructions(&message, UnmatchedFeedMode::Reject, &[2]))
+        .await;
+    assert_error(result, 1, Error::PriceStale);
+
+    let message = sign_update(
+        &env.test_signer,
+        &price_payload(now_us - MAX_AGE_US, &[(2, 100)]),
+    );
+    env.send(&env.update_instructions(&message, UnmatchedFeedMode::Reject, &[2]))
+        .await
+        .unwrap();
+
+    // Small clock skew is tolerated.
+    let message = sign_update(
+        &env.test_signer,
+        &price_payload(now_us + MAX_FUTURE_SKEW_US, &[(2, 101)]),
+    );
+    env.send(&env.update_instructions(&message, UnmatchedFeedMode::Reject, &[2]))
+        .await
+        .unwrap();
+
+    let message = sign_update(
+        &env.test_signer,
+        &price_payload(now_us + MAX_FUTURE_SKEW_US + 1, &[(2, 102)]),
+    );
+    let result = env
+        .send(&env.update_instructions(&message, UnmatchedFeedMode::Reject, &[2]))
+        .await;
+    assert_error(result, 1, Error::TimestampInFuture);
+
+    let state = env.state(2).await;
+    assert_eq!({ state.latest_timestamp }, now_us + MAX_FUTURE_SKEW_US);
+    assert_eq!({ state.latest_price }, 101);
+}
+
+#[tokio::test]
+async fn test_initialize_authority() {
+    let mut env = setup().await;
+    let authority = Keypair::new();
+    let initialize = instructions::initialize(
+        &pyth_lazer_solana_example::ID,
+        &env.context.payer.pubkey(),
+        &InitializeArgs {
+            price_feed_id: 2,
+            max_age_us: MAX_AGE_US,
+            accepted_channels: channel_bit(Channel::FixedRate(FixedRate::MIN).id()),
+            max_deviation_bps_per_second: 0,
+            deviation_mode: DeviationMode::Reject as u8,
+            authority: authority.pubkey(),
+        },
+    );
+
+    // Nobody can claim a price feed for an authority that doesn't sign.
+    let mut unsigned = initialize.clone();
+    unsigned.accounts[3].is_signer = false;
+    let result = env.send(&[unsigned]).await;
+    assert_eq!(
+        result.unwrap_err().unwrap(),
+        TransactionError::InstructionError(0, InstructionError::MissingRequiredSignature),
+    );
+    let mut other = initialize.clone();
+    other.accounts[3].pubkey = env.context.payer.pubkey();
+    let result = env.send(&[other]).await;
+    assert_error(result, 0, Error::Unauthorized);
+
+    env.send_signed(&[initialize], &[&authority]).await.unwrap();
+    assert_eq!({ env.state(2).await.authority }, authority.pubkey());
+}
+
+#[tokio::test]
+async fn test_config_authority() {
+    let mut env = setup().await;
+    env.initialize_feed(2).await;
+    let authority = env.context.payer.pubkey();
+    let new_authority = Keypair::new();
+
+    let set_config = |authority: &Pubkey, max_age_us: u64| {
+        instructions::set_config(
+            &pyth_lazer_solana_example::ID,
+            authority,
+            &SetConfigArgs {
+                price_feed_id: 2,
+                max_age_us,
+                accepted_channels: channel_bit(Channel::FixedRate(FixedRate::MIN).id()),
+                max_deviation_bps_per_second: 0,
+                deviation_mode: DeviationMode::Reject as u8,
+            },
+        )
+    };
+
+    let result = env
+        .send_signed(&[set_config(&new_authority.pubkey(), 1)], &[&new_authority])
+        .await;
+    assert_error(result, 0, Error::Unauthorized);
+    env.send(&[set_config(&authority, 1)]).await.unwrap();
+    assert_eq!({ env.state(2).await.max_age_us }, 1);
+
+    // The new authority takes over only after accepting.
+    env.send(&[instructions::propose_authority(
+        &pyth_lazer_solana_example::ID,
+        &authority,
+        2,
+        &new_authority.pubkey(),
+    )])
+    .await
+    .unwrap();
+    let state = env.state(2).await;
+    assert_eq!({ state.authority }, authority);
+    assert_eq!({ state.pending_authority }, new_authority.pubkey());
+
+    let other = Keypair::new();
+    let result = env
+        .send_signed(
+            &[instructions::accept_authority(
+                &pyth_lazer_solana_example::ID,
+                &other.pubkey(),
+                2,
+            )],
+            &[&other],
+        )
+        .await;
+    assert_error(result, 0, Error::Unauthorized);
+    env.send_signed(
+        &[instructions::accept_authority(
+            &pyth_lazer_solana_example::ID,
+            &new_authority.pubkey(),
+            2,
+        )],
+        &[&new_authority],
+    )
+    .await
+    .unwrap();
+    let state = env.state(2).await;
+    assert_eq!({ state.authority }, new_authority.pubkey());
+    assert_eq!({ state.pending_authority }, Pubkey::default());
+
+    let result = env.send(&[set_config(&authority, 2)]).await;
+    assert_error(result, 0, Error::Unauthorized);
+    env.send_signed(&[set_config(&new_authority.pubkey(), 2)], &[&new_authority])
+        .await
+        .unwrap();
+    assert_eq!({ env.state(2).await.max_age_us }, 2);
+}
+
+#[tokio::test]
+async fn test_accepted_channels() {
+    let mut env = setup().await;
+    env.initialize_feed(2).await;
+    env.set_clock(3).await;
+    let fixed_rate_50 = Channel::FixedRate(FixedRate::from_ms(50).unwrap());
+    let fixed_rate_200 = Channel::FixedRate(FixedRate::from_ms(200).unwrap());
+
+    let message = sign_update(
+        &env.test_signer,
+        &channel_price_payload(fixed_rate_50, 1_000_000, &[(2, 100)]),
+    );
+    let result = env
+        .send(&env.update_instructions(&message, UnmatchedFeedMode::Reject, &[2]))
+        .await;
+    assert_error(result, 1, Error::WrongChannel);
+
+    env.send(&[instructions::set_config(
+        &pyth_lazer_solana_example::ID,
+        &env.context.payer.pubkey(),
+        &SetConfigArgs {
+            price_feed_id: 2,
+            max_age_us: MAX_AGE_US,
+            accepted_channels: channel_bit(fixed_rate_50.id()) | channel_bit(fixed_rate_200.id()),
+            max_deviation_bps_per_second: 0,
+            deviation_mode: DeviationMode::Reject as u8,
+        },
+    )])
+    .await
+    .unwrap();
+
+    let message = sign_update(
+        &env.test_signer,
+        &channel_price_payload(fixed_rate_50, 1_500_000, &[(2, 100)]),
+    );
+    env.send(&env.update_instructions(&message, UnmatchedFeedMode::Reject, &[2]))
+        .await
+        .unwrap();
+    let message = sign_update(
+        &env.test_signer,
+        &channel_price_payload(fixed_rate_200, 2_000_000, &[(2, 101)]),
+    );
+    env.send(&env.update_instructions(&message, UnmatchedFeedMode::Reject, &[2]))
+        .await
+        .unwrap();
+    // Updates of the 1 ms channel are no longer accepted.
+    let message = sign_update(&env.test_signer, &price_payload(3_000_000, &[(2, 102)]));
+    let result = env
+        .send(&env.update_instructions(&message, UnmatchedFeedMode::Reject, &[2]))
+        .await;
+    assert_error(result, 1, Error::WrongChannel);
+
+    let state = env.state(2).await;
+    assert_eq!({ state.latest_timestamp }, 2_000_000);
+    assert_eq!({ state.latest_price }, 101);
+}
+
+#[tokio::test]
+async fn test_close() {
+    let mut env = setup().await;
+    env.initialize_feed(2).await;
+    env.initialize_history(2).await;
+    env.set_clock(3).await;
+    let authority = env.context.payer.pubkey();
+    let data_pda_key = find_data_pda(&pyth_lazer_solana_example::ID, 2).0;
+    let history_pda_key = find_history_pda(&pyth_lazer_solana_example::ID, 2).0;
+    let destination = Pubkey::new_unique();
+    let update_with_history = |env: &TestEnv, message: &[u8]| {
+        let mut instructions = env.update_instructions(message, UnmatchedFeedMode::Reject, &[2]);
+        instructions[1]
+            .accounts
+            .push(AccountMeta::new(history_pda_key, false));
+        instructions
+    };
+
+    let message = sign_update(&env.test_signer, &price_payload(1_000_000, &[(2, 100)]));
+    env.send(&update_with_history(&env, &message))
+        .await
+        .unwrap();
+    let mut closed_lamports = 0;
+    for key in [data_pda_key, history_pda_key] {
+        closed_lamports += env.context.banks_client.get_balance(key).await.unwrap();
+    }
+
+    let other = Keypair::new();
+    let result = env
+        .send_signed(
+            &[instructions::close(
+                &pyth_lazer_solana_example::ID,
+                &other.pubkey(),
+                2,
+                &destination,
+            )],
+            &[&other],
+        )
+        .await;
+    assert_error(result, 0, Error::Unauthorized);
+
+    // The account can't be used by later instructions of the closing transaction.
+    let result = env
+        .send(&[
+            instructions::close(&pyth_lazer_solana_example::ID, &authority, 2, &destination),
+            instructions::set_config(
+                &pyth_lazer_solana_example::ID,
+                &authority,
+                &SetConfigArgs {
+                    price_feed_id: 2,
+                    max_age_us: MAX_AGE_US,
+                    accepted_channels: channel_bit(Channel::FixedRate(FixedRate::MIN).id()),
+                    max_deviation_bps_per_second: 0,
+                    deviation_mode: DeviationMode::Reject as u8,
+                },
+            ),
+        ])
+        .await;
+    assert_error(result, 1, Error::AccountClosed);
+
+    env.send(&[instructions::close(
+        &pyth_lazer_solana_example::ID,
+        &authority,
+        2,
+        &destination,
+    )])
+    .await
+    .unwrap();
+    let banks_client = &mut env.context.banks_client;
+    for key in [data_pda_key, history_pda_key] {
+        assert!(banks_client.get_account(key).await.unwrap().is_none());
+    }
+    assert_eq!(
+        banks_client.get_balance(destination).await.unwrap(),
+        closed_lamports
+    );
+
+    let message = sign_update(&env.test_signer, &price_payload(1_500_000, &[(2, 101)]));
+    let result = env
+        .send(&env.update_instructions(&message, UnmatchedFeedMode::Reject, &[2]))
+        .await;
+    assert_eq!(
+        result.unwrap_err().unwrap(),
+        TransactionError::InstructionError(1, InstructionError::InvalidAccountData),
+    );
+
+    // A reinitialized account starts from scratch. A new blockhash is needed
+    // because the transaction is otherwise identical to the first initialization.
+    env.context.last_blockhash = env.context.get_new_latest_blockhash().await.unwrap();
+    env.initialize_feed(2).await;
+    env.initialize_history(2).await;
+    let state = env.state(2).await;
+    assert_eq!({ state.latest_timestamp }, 0);
+    assert_eq!({ state.latest_price }, 0);
+
+    let message = sign_update(&env.test_signer, &price_payload(2_000_000, &[(2, 102)]));
+    env.send(&update_with_history(&env, &message))
+        .await
+        .unwrap();
+    let state = env.state(2).await;
+    assert_eq!({ state.latest_timestamp }, 2_000_000);
+    assert_eq!({ state.latest_price }, 102);
+
+    // The TWAP doesn't include prices from before the price feed was closed.
+    let get_twap = |window_us| instructions::get_twap(&pyth_lazer_solana_example::ID, 2, window_us);
+    let twap = env.simulate(&[get_twap(1_000_000)]).await.unwrap();
+    assert_eq!({ from_bytes::<TwapResult>(&twap).price }, 102);
+    let result = env.simulate(&[get_twap(2_000_000)]).await;
+    assert_eq!(
+        result.unwrap_err(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(Error::InsufficientHistory as u32)
+        ),
+    );
+
+    // A price feed without history can be closed as well.
+    env.initialize_feed(3).await;
+    env.send(&[instructions::close(
+        &pyth_lazer_solana_example::ID,
+        &authority,
+        3,
+        &destination,
+    )])
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn test_history_twap() {
+    let mut env = setup().await;
+    env.initialize_feed(2).await;
+    env.initialize_feed(3).await;
+    env.initialize_history(2).await;
+    env.set_clock(2).await;
+    let history_pda_key = find_history_pda(&pyth_lazer_solana_example::ID, 2).0;
+
+    // Feed 3 has no history, so only the price of feed 2 is recorded.
+    for (timestamp_us, price) in [(1_000_000, 100), (2_000_000, 200)] {
+        let message = sign_update(
+            &env.test_signer,
+            &price_payload(timestamp_us, &[(2, price), (3, price)]),
+        );
+        let mut instructions =
+            env.update_instructions(&message, UnmatchedFeedMode::Reject, &[2, 3]);
+        instructions[1]
+            .accounts
+            .push(AccountMeta::new(history_pda_key, false));
+        env.send(&instructions).await.unwrap();
+    }
+
+    let get_twap = |window_us| instructions::get_twap(&pyth_lazer_solana_example::ID, 2, window_us);
+
+    // 100 for one second, then 200 for two seconds.
+    env.set_clock(4).await;
+    let twap = env.simulate(&[get_twap(3_000_000)]).await.unwrap();
+    let twap = from_bytes::<TwapResult>(&twap);
+    assert_eq!({ twap.price }, 166);
+    assert_eq!({ twap.exponent }, 0);
+    assert_eq!({ twap.start_timestamp_us }, 1_000_000);
+    assert_eq!({ twap.end_timestamp_us }, 4_000_000);
+
+    let twap = env.simulate(&[get_twap(1_000_000)]).await.unwrap();
+    assert_eq!({ from_bytes::<TwapResult>(&twap).price }, 200);
+
+    // The window starts before the first recorded price.
+    let result = env.simulate(&[get_twap(3_500_000)]).await;
+    assert_eq!(
+        result.unwrap_err(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(Error::InsufficientHistory as u32)
+        ),
+    );
+
+    // A history account can't be updated without the data account of its price feed.
+    let message = sign_update(&env.test_signer, &price_payload(3_000_000, &[(3, 300)]));
+    let mut instructions = env.update_instructions(&message, UnmatchedFeedMode::Skip, &[3]);
+    instructions[1]
+        .accounts
+        .push(AccountMeta::new(history_pda_key, false));
+    let result = env.send(&instructions).await;
+    assert_error(result, 1, Error::WrongPriceFeed);
+}
+
+#[tokio::test]
+async fn test_deviation_breaker() {
+    let mut env = setup().await;
+    env.initialize_feed(2).await;
+    env.set_clock(10).await;
+    let authority = env.context.payer.pubkey();
+
+    let set_deviation_mode = |deviation_mode: DeviationMode| {
+        instructions::set_config(
+            &pyth_lazer_solana_example::ID,
+            &authority,
+            &SetConfigArgs {
+                price_feed_id: 2,
+                max_age_us: MAX_AGE_US,
+                accepted_channels: channel_bit(Channel::FixedRate(FixedRate::MIN).id()),
+                // 1% per second.
+                max_deviation_bps_per_second: 100,
+                deviation_mode: deviation_mode as u8,
+            },
+        )
+    };
+    env.send(&[set_deviation_mode(DeviationMode::Reject)])
+        .await
+        .unwrap();
+
+    let update = |env: &TestEnv, timestamp_us, price| {
+        let message = sign_update(
+            &env.test_signer,
+            &price_payload(timestamp_us, &[(2, price)]),
+        );
+        env.update_instructions(&message, UnmatchedFeedMode::Reject, &[2])
+    };
+
+    // The first price is always accepted.
+    env.send(&update(&env, 1_000_000, 10_000)).await.unwrap();
+    env.send(&update(&env, 2_000_000, 10_050)).await.unwrap();
+    let result = env.send(&update(&env, 3_000_000, 11_000)).await;
+    assert_error(result, 1, Error::DeviationExceeded);
+    assert_eq!({ env.state(2).await.latest_price }, 10_050);
+
+    // A quarantined update is applied once a later update confirms it.
+    env.send(&[set_deviation_mode(DeviationMode::Quarantine)])
+        .await
+        .unwrap();
+    env.send(&update(&env, 4_000_000, 11_000)).await.unwrap();
+    let state = env.state(2).await;
+    assert_eq!({ state.latest_price }, 10_050);
+    assert_eq!({ state.pending_timestamp }, 4_000_000);
+    assert_eq!({ state.pending_price }, 11_000);
+
+    // An update can't confirm itself.
+    let result = env.send(&update(&env, 4_000_000, 11_001)).await;
+    assert_error(result, 1, Error::NonMonotonicTimestamp);
+
+    env.send(&update(&env, 5_000_000, 11_050)).await.unwrap();
+    let state = env.state(2).await;
+    assert_eq!({ state.latest_timestamp }, 5_000_000);
+    assert_eq!({ state.latest_price }, 11_050);
+    assert_eq!({ state.pending_timestamp }, 0);
+}
+
+#[tokio::test]
+async fn test_update_args() {
+    let mut env = setup().await;
+    env.initialize_feed(2).await;
+    env.set_clock(10).await;
+    let update_args = UpdateArgs::new(UnmatchedFeedMode::Reject);
+    let message = sign_update(&env.test_signer, &price_payload(1_000_000, &[(2, 100)]));
+
+    // Clients built for the old layout send `hello: u64 = 42` and the unmatched feed mode.
+    let mut instructions = env.update_instructions(&message, UnmatchedFeedMode::Reject, &[2]);
+    instructions[1].data[1..9].copy_from_slice(&42u64.to_le_bytes());
+    instructions[1].data[9] = UnmatchedFeedMode::Reject as u8;
+    let result = env.send(&instructions).await;
+    assert_error(result, 1, Error::UnsupportedVersion);
+
+    let result = env
+        .send(&env.update_instructions_with_args(
+            &message,
+            &UpdateArgs {
+                expected_price_feed_id: 3,
+                ..update_args
+            },
+            &[2],
+        ))
+        .await;
+    assert_error(result, 1, Error::WrongPriceFeed);
+    let result = env
+        .send(&env.update_instructions_with_args(
+            &message,
+            &UpdateArgs {
+                min_timestamp_us: 1_000_001,
+                ..update_args
+            },
+            &[2],
+        ))
+        .await;
+    assert_error(result, 1, Error::PriceStale);
+    let result = env
+        .send(&env.update_instructions_with_args(
+            &message,
+            &UpdateArgs {
+                max_age_us: 8_999_999,
+                ..update_args
+            },
+            &[2],
+        ))
+        .await;
+    assert_error(result, 1, Error::PriceStale);
+
+    // The ed25519 instruction doesn't have to be the first one.
+    let mut instructions = vec![ComputeBudgetInstruction::set_compute_unit_limit(400_000)];
+    instructions.extend(env.update_instructions_with_args(
+        &message,
+        &UpdateArgs {
+            expected_price_feed_id: 2,
+            min_timestamp_us: 1_000_000,
+            max_age_us: 9_000_000,
+            ed25519_instruction_index: 1,
+            ..update_args
+        },
+        &[2],
+    ));
+    env.send(&instructions).await.unwrap();
+    assert_eq!({ env.state(2).await.latest_price }, 100);
+}
+
+#[tokio::test]
+async fn test_discover_ed25519_instruction() {
+    let mut env = setup().await;
+    env.initialize_feed(2).await;
+    env.initialize_feed(3).await;
+    env.set_clock(10).await;
+    let update_args = UpdateArgs {
+        ed25519_instruction_index: DISCOVER_ED25519_INSTRUCTION,
+        ..UpdateArgs::new(UnmatchedFeedMode::Reject)
+    };
+
+    // A single ed25519 instruction checks the signatures of two updates that follow it.
+    let message_offset = (size_of::<UpdateArgs>() + 1).try_into().unwrap();
+    let mut signatures = Vec::new();
+    let mut instructions = vec![ComputeBudgetInstruction::set_compute_unit_limit(400_000)];
+    for (instruction_index, price_feed_id) in [(2, 2), (3, 3)] {
+        let message = sign_update(
+            &env.test_signer,
+            &price_payload(1_000_000, &[(price_feed_id, 100)]),
+        );
+        signatures.push(pyth_lazer_solana_contract::Ed25519SignatureOffsets::new(
+            &message,
+            instruction_index,
+            message_offset,
+        ));
+        instructions.push(
+            env.update_instructions_with_args(&message, &update_args, &[price_feed_id])
+                .pop()
+                .unwrap(),
+        );
+    }
+    instructions.insert(
+        1,
+        Instruction::new_with_bytes(
+            solana_program::ed25519_program::ID,
+            &ed25519_program_args(&signatures),
+            vec![],
+        ),
+    );
+    env.send(&instructions).await.unwrap();
+    assert_eq!({ env.state(2).await.latest_price }, 100);
+    assert_eq!({ env.state(3).await.latest_price }, 100);
+
+    // The ed25519 instruction may also check a signature whose data it contains itself,
+    // after the offsets of all signatures.
+    let with_inline_signature = |env: &TestEnv, message: &[u8], other_message: &[u8]| {
+        let other_signer = Keypair::new();
+        let header_len = 2 + 2 * size_of::<pyth_lazer_solana_contract::Ed25519SignatureOffsets>();
+        let other_offset = |offset: usize| u16::try_from(header_len + offset).unwrap();
+        let signatures = [
+            pyth_lazer_solana_contract::Ed25519SignatureOffsets::new(message, 2, message_offset),
+            pyth_lazer_solana_contract::Ed25519SignatureOffsets {
+                signature_offset: other_offset(32),
+                signature_instruction_index: u16::MAX,
+                public_key_offset: other_offset(0),
+                public_key_instruction_index: u16::MAX,
+                message_data_offset: other_offset(32 + 64),
+                message_data_size: other_message.len().try_into().unwrap(),
+                message_instruction_index: u16::MAX,
+            },
+        ];
+        let mut ed25519_data = ed25519_program_args(&signatures);
+        ed25519_data.extend_from_slice(other_signer.pubkey().as_ref());
+        ed25519_data.extend_from_slice(other_signer.sign_message(other_message).as_ref());
+        ed25519_data.extend_from_slice(other_message);
+        vec![
+            ComputeBudgetInstruction::set_compute_unit_limit(400_000),
+            Instruction::new_with_bytes(solana_program::ed25519_program::ID, &ed25519_data, vec![]),
+            env.update_instructions_with_args(message, &update_args, &[2])
+                .pop()
+                .unwrap(),
+        ]
+    };
+    let message = sign_update(&env.test_signer, &price_payload(1_500_000, &[(2, 101)]));
+    // The Pyth Lazer program only accepts inline data that is a multiple of the size of
+    // the offsets, i.e. the offsets followed by 7 more entries here.
+    env.send(&with_inline_signature(&env, &message, &[1; 16]))
+        .await
+        .unwrap();
+    assert_eq!({ env.state(2).await.latest_price }, 101);
+    // The signature is found with any other length, but the Pyth Lazer program rejects it.
+    // The failure aborts the transaction, except in native tests where the example program
+    // reports it as `VerificationFailed`.
+    let message = sign_update(&env.test_signer, &price_payload(1_600_000, &[(2, 102)]));
+    let result = env
+        .send(&with_inline_signature(&env, &message, &[1; 15]))
+        .await;
+    let expected = [
+        InstructionError::InvalidInstructionData,
+        InstructionError::Custom(Error::VerificationFailed as u32),
+    ]
+    .map(|err| TransactionError::InstructionError(2, err));
+    assert!(expected.contains(&result.unwrap_err().unwrap()));
+
+    // Without an ed25519 instruction there is nothing to find.
+    let message = sign_update(&env.test_signer, &price_payload(2_000_000, &[(2, 101)]));
+    let update = env
+        .update_instructions_with_args(&message, &update_args, &[2])
+        .pop()
+        .unwrap();
+    let result = env.send(&[update]).await;
+    assert_error(result, 0, Error::SignatureNotFound);
+}
+
+#[tokio::test]
+async fn test_update_batch() {
+    let mut env = setup().await;
+    env.initialize_feed(2).await;
+    env.initialize_feed(3).await;
+    env.set_clock(10).await;
+
+    let messages = [
+        price_payload(1_000_000, &[(2, 100)]),
+        price_payload(1_000_000, &[(3, 200)]),
+        price_payload(2_000_000, &[(2, 101)]),
+    ]
+    .map(|payload| sign_update(&env.test_signer, &payload));
+    let mut instructions = instructions::update_batch(
+        &pyth_lazer_solana_example::ID,
+        &env.context.payer.pubkey(),
+        &env.treasury,
+        &messages.iter().map(Vec::as_slice).collect::<Vec<_>>(),
+        &UpdateArgs::new(UnmatchedFeedMode::Reject),
+        &[2, 3],
+    );
+    env.send(&instructions).await.unwrap();
+
+    let state = env.state(2).await;
+    assert_eq!({ state.latest_timestamp }, 2_000_000);
+    assert_eq!({ state.latest_price }, 101);
+    let state = env.state(3).await;
+    assert_eq!({ state.latest_timestamp }, 1_000_000);
+    assert_eq!({ state.latest_price }, 200);
+
+    // A batch must contain at least one message.
+    let mut update = instructions.pop().unwrap();
+    update.data.truncate(1 + size_of::<UpdateArgs>());
+    let result = env.send(&[update]).await;
+    assert_error(result, 0, Error::InvalidMessage);
+}
+
+#[tokio::test]
+async fn test_get_price() {
+    let mut env = setup().await;
+    env.initialize_feed(2).await;
+    env.set_clock(2).await;
+    let get_price =
+        |max_age_us| instructions::get_price(&pyth_lazer_solana_example::ID, 2, max_age_us);
+
+    let result = env.simulate(&[get_price(MAX_AGE_US)]).await;
+    assert_eq!(
+        result.unwrap_err(),
+        TransactionError::InstructionError(0, InstructionError::Custom(Error::MissingPrice as u32)),
+    );
+
+    let message = sign_update(&env.test_signer, &price_payload(1_500_000, &[(2, 100)]));
+    let instructions = env.update_instructions(&message, UnmatchedFeedMode::Reject, &[2]);
+    env.send(&instructions).await.unwrap();
+
+    let price = cpi::decode_price(&env.simulate(&[get_price(MAX_AGE_US)]).await.unwrap()).unwrap();
+    assert_eq!(
+        { price.version },
+        pyth_lazer_solana_example::PRICE_RESULT_VERSION
+    );
+    assert_eq!({ price.price_feed_id }, 2);
+    assert_eq!({ price.timestamp_us }, 1_500_000);
+    assert_eq!({ price.price }, 100);
+
+    // The price is 0.5 seconds old.
+    let result = env.simulate(&[get_price(400_000)]).await;
+    assert_eq!(
+        result.unwrap_err(),
+        TransactionError::InstructionError(0, InstructionError::Custom(Error::PriceStale as u32)),
+    );
+
+    // Another program reads the same price via CPI.
+    let consumer = |max_age_us: u64| {
+        Instruction::new_with_bytes(
+            PRICE_CONSUMER_ID,
+            &max_age_us.to_le_bytes(),
+            vec![
+                AccountMeta::new_readonly(pyth_lazer_solana_example::ID, false),
+                AccountMeta::new_readonly(
+                    find_data_pda(&pyth_lazer_solana_example::ID, 2).0,
+                    false,
+                ),
+            ],
+        )
+    };
+    let consumed =
+        cpi::decode_price(&env.simulate(&[consumer(MAX_AGE_US)]).await.unwrap()).unwrap();
+    assert_eq!(consumed, price);
+    let result = env.simulate(&[consumer(400_000)]).await;
+    assert_eq!(
+        result.unwrap_err(),
+        TransactionError::InstructionError(0, InstructionError::Custom(Error::PriceStale as u32)),
+    );
+}
+
+#[test]
+fn test_decode_price_versions() {
+    let price = PriceResult {
+        version: 2,
+        price_feed_id: 2,
+        timestamp_us: 1_500_000,
+        price: 100,
+        paused: 1,
+        ..PriceResult::zeroed()
+    };
+    assert_eq!(cpi::decode_price(bytes_of(&price)).unwrap(), price);
+
+    // A version 1 result ends before `paused`.
+    let version_1 = PriceResult {
+        version: 1,
+        ..price
+    };
+    let decoded = cpi::decode_price(&bytes_of(&version_1)[..size_of::<PriceResult>() - 1]).unwrap();
+    assert_eq!({ decoded.price }, 100);
+    assert_eq!(decoded.paused, 0);
+
+    // The fields of newer versions are ignored.
+    let version_3 = PriceResult {
+        version: 3,
+        ..price
+    };
+    let mut newer = bytes_of(&version_3).to_vec();
+    newer.extend_from_slice(&[0xff; 16]);
+    assert_eq!(cpi::decode_price(&newer).unwrap(), version_3);
+
+    let version_0 = PriceResult {
+        version: 0,
+        ..price
+    };
+    for invalid in [&[][..], bytes_of(&version_0)] {
+        assert_eq!(
+            cpi::decode_price(invalid).unwrap_err(),
+            ProgramError::Custom(Error::UnsupportedVersion as u32),
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_price_update_events() {
+    let mut env = setup().await;
+    env.initialize_feed(2).await;
+    env.initialize_feed(3).await;
+    env.set_clock(2).await;
+
+    let message = sign_update(
+        &env.test_signer,
+        &price_payload(1_000_000, &[(2, 100), (3, 200)]),
+    );
+    let logs = env
+        .send_with_logs(&env.update_instructions(&message, UnmatchedFeedMode::Reject, &[2, 3]))
+        .await;
+    let events = parse_price_update_events(&pyth_lazer_solana_example::ID, &logs);
+    let expected = |price_feed_id, price| PriceUpdateEvent {
+        price_feed_id,
+        price,
+        exponent: 0,
+        timestamp_us: 1_000_000,
+        channel_id: Channel::FixedRate(FixedRate::MIN).id().0,
+        verification_path: VerificationPath::Ed25519 as u8,
+    };
+    assert_eq!(events, [expected(2, 100), expected(3, 200)]);
+
+    // Events of other programs are ignored.
+    assert!(parse_price_update_events(&pyth_lazer_solana_contract::ID, &logs).is_empty());
+}
+
+#[tokio::test]
+async fn test_update_auto() {
+    let mut env = setup().await;
+    env.initialize_feed(2).await;
+    env.set_clock(2).await;
+    let update_auto = |env: &TestEnv, message: &[u8]| {
+        instructions::update_auto(
+            &pyth_lazer_solana_example::ID,
+            &env.context.payer.pubkey(),
+            &env.treasury,
+            message,
+            &UpdateArgs::new(UnmatchedFeedMode::Reject),
+            &[2],
+        )
+    };
+
+    // An update in the Solana format is verified with the ed25519 instruction.
+    let message = sign_update(&env.test_signer, &price_payload(1_000_000, &[(2, 100)]));
+    let instructions = update_auto(&env, &message);
+    assert_eq!(instructions.len(), 2);
+    env.send(&instructions).await.unwrap();
+    assert_eq!({ env.state(2).await.latest_price }, 100);
+
+    // An update in the LE-ECDSA format is verified by the Pyth Lazer program.
+    env.set_ecdsa_signer().await;
+    let message_ecdsa = hex::decode(ECDSA_UPDATE_HEX).unwrap();
+    env.set_clock(1740480251).await;
+    let instructions = update_auto(&env, &message_ecdsa);
+    assert_eq!(instructions.len(), 1);
+    env.send(&instructions).await.unwrap();
+    let state = env.state(2).await;
+    assert_eq!({ state.latest_timestamp }, 1740480250860000);
+    assert_eq!({ state.latest_price }, 11488100000000);
+
+    // Other formats are rejected.
+    let mut message_unknown = message_ecdsa.clone();
+    message_unknown[0] ^= 1;
+    let result = env.send(&update_auto(&env, &message_unknown)).await;
+    assert_error(result, 0, Error::InvalidMessage);
+}
+
+#[tokio::test]
+async fn test_updater_allowlist() {
+    let mut env = setup().await;
+    env.initialize_feed(2).await;
+    env.set_clock(2).await;
+    let authority = env.context.payer.pubkey();
+    let updater = Keypair::new();
+    // The updater pays the Pyth Lazer fee.
+    env.send(&[system_instruction::transfer(
+        &authority,
+        &updater.pubkey(),
+        1_000_000_000,
+    )])
+    .await
+    .unwrap();
+    let set_allowlist = |authority: &Pubkey, updaters: &[Pubkey], enabled: bool| {
+        instructions::set_updater_allowlist(
+            &pyth_lazer_solana_example::ID,
+            authority,
+            2,
+            updaters,
+            enabled,
+        )
+    };
+    let update = |env: &TestEnv, payer: &Pubkey, timestamp_us: u64, with_allowlist: bool| {
+        let message = sign_update(&env.test_signer, &price_payload(timestamp_us, &[(2, 100)]));
+        let mut instructions = instructions::update(
+            &pyth_lazer_solana_example::ID,
+            payer,
+            &env.treasury,
+            &message,
+            &UpdateArgs::new(UnmatchedFeedMode::Reject),
+            &[2],
+        );
+        if with_allowlist {
+            instructions[1]
+                .accounts
+                .push(instructions::updater_allowlist_account(
+                    &pyth_lazer_solana_example::ID,
+                    2,
+                ));
+        }
+        instructions
+    };
+
+    let other = Keypair::new();
+    let result = env
+        .send_signed(
+            &[set_allowlist(&other.pubkey(), &[other.pubkey()], true)],
+            &[&other],
+        )
+        .await;
+    assert_error(result, 0, Error::Unauthorized);
+    // An enabled allowlist must not be empty.
+    let result = env.send(&[set_allowlist(&authority, &[], true)]).await;
+    assert_eq!(
+        result.unwrap_err().unwrap(),
+        TransactionError::InstructionError(0, InstructionError::InvalidInstructionData),
+    );
+    env.send(&[set_allowlist(&authority, &[updater.pubkey()], true)])
+        .await
+        .unwrap();
+    assert_eq!(env.state(2).await.updater_allowlist_enabled, 1);
+
+    // Other payers are rejected, whether or not the allowlist account is passed.
+    let result = env.send(&update(&env, &authority, 1_000_000, false)).await;
+    assert_error(result, 1, Error::UnauthorizedUpdater);
+    let result = env.send(&update(&env, &authority, 1_000_000, true)).await;
+    assert_error(result, 1, Error::UnauthorizedUpdater);
+    let result = env
+        .send_signed(
+            &update(&env, &updater.pubkey(), 1_000_000, false),
+            &[&updater],
+        )
+        .await;
+    assert_error(result, 1, Error::UnauthorizedUpdater);
+
+    env.send_signed(
+        &update(&env, &updater.pubkey(), 1_000_000, true),
+        &[&updater],
+    )
+    .await
+    .unwrap();
+    assert_eq!({ env.state(2).await.latest_timestamp }, 1_000_000);
+
+    // Once disabled, anyone can update again.
+    env.send(&[set_allowlist(&authority, &[updater.pubkey()], false)])
+        .await
+        .unwrap();
+    env.send(&update(&env, &authority, 1_500_000, false))
+        .await
+        .unwrap();
+    assert_eq!({ env.state(2).await.latest_timestamp }, 1_500_000);
+
+    // Closing the price feed closes its allowlist, so a reinitialized price feed starts without one.
+    let allowlist_pda_key = find_updater_allowlist_pda(&pyth_lazer_solana_example::ID, 2).0;
+    env.send(&[instructions::close(
+        &pyth_lazer_solana_example::ID,
+        &authority,
+        2,
+        &authority,
+    )])
+    .await
+    .unwrap();
+    let banks_client = &mut env.context.banks_client;
+    assert!(banks_client
+        .get_account(allowlist_pda_key)
+        .await
+        .unwrap()
+        .is_none());
+    // The transactions are otherwise identical to the first ones.
+    env.context.last_blockhash = env.context.get_new_latest_blockhash().await.unwrap();
+    env.initialize_feed(2).await;
+    assert_eq!(env.state(2).await.updater_allowlist_enabled, 0);
+    env.send(&[set_allowlist(&authority, &[updater.pubkey()], true)])
+        .await
+        .unwrap();
+    let result = env.send(&update(&env, &authority, 2_000_000, true)).await;
+    assert_error(result, 1, Error::UnauthorizedUpdater);
+}
+
+#[tokio::test]
+async fn test_pause() {
+    let mut env = setup().await;
+    env.initialize_feed(2).await;
+    env.set_clock(2).await;
+    let authority = env.context.payer.pubkey();
+    let set_paused = |authority: &Pubkey, paused: bool| {
+        instructions::set_paused(&pyth_lazer_solana_example::ID, authority, 2, paused)
+    };
+    let get_price = instructions::get_price(&pyth_lazer_solana_example::ID, 2, MAX_AGE_US);
+
+    let message = sign_update(&env.test_signer, &price_payload(1_000_000, &[(2, 100)]));
+    let instructions = env.update_instructions(&message, UnmatchedFeedMode::Reject, &[2]);
+    env.send(&instructions).await.unwrap();
+
+    let other = Keypair::new();
+    let result = env
+        .send_signed(&[set_paused(&other.pubkey(), true)], &[&other])
+        .await;
+    assert_error(result, 0, Error::Unauthorized);
+    env.send(&[set_paused(&authority, true)]).await.unwrap();
+    assert_eq!(env.state(2).await.paused, 1);
+
+    let message = sign_update(&env.test_signer, &price_payload(1_500_000, &[(2, 200)]));
+    let instructions = env.update_instructions(&message, UnmatchedFeedMode::Reject, &[2]);
+    let result = env.send(&instructions).await;
+    assert_error(result, 1, Error::Paused);
+
+    // The frozen price is still readable and reported as paused.
+    let price = cpi::decode_price(&env.simulate(&[get_price.clone()]).await.unwrap()).unwrap();
+    assert_eq!({ price.price }, 100);
+    assert_eq!(price.paused, 1);
+    // Programs reading it via CPI see the pause too.
+    let consumer = Instruction::new_with_bytes(
+        PRICE_CONSUMER_ID,
+        &MAX_AGE_US.to_le_bytes(),
+        vec![
+            AccountMeta::new_readonly(pyth_lazer_solana_example::ID, false),
+            AccountMeta::new_readonly(find_data_pda(&pyth_lazer_solana_example::ID, 2).0, false),
+        ],
+    );
+    let consumed = cpi::decode_price(&env.simulate(&[consumer]).await.unwrap()).unwrap();
+    assert_eq!(consumed, price);
+    // The pause doesn't weaken the maximum age of the caller.
+    env.set_clock(100).await;
+    let result = env.simulate(&[get_price.clone()]).await;
+    assert_eq!(
+        result.unwrap_err(),
+        TransactionError::InstructionError(0, InstructionError::Custom(Error::PriceStale as u32)),
+    );
+
+    env.send(&[set_paused(&authority, false)]).await.unwrap();
+    let result = env.simulate(&[get_price]).await;
+    assert_eq!(
+        result.unwrap_err(),
+        TransactionError::InstructionError(0, InstructionError::Custom(Error::PriceStale as u32)),
+    );
+    let message = sign_update(&env.test_signer, &price_payload(99_000_000, &[(2, 200)]));
+    let instructions = env.update_instructions(&message, UnmatchedFeedMode::Reject, &[2]);
+    env.send(&instructions).await.unwrap();
+    assert_eq!({ env.state(2).await.latest_price }, 200);
+}
+
+/// Maximum compute units of a transaction containing each instruction of the example program,
+/// including the CPIs it makes. Exceeding one of them is a regression.
+const COMPUTE_UNIT_BUDGETS: &[(&str, u64)] = &[
+    ("Initialize", 20_000),
+    ("InitializeHistory", 20_000),
+    ("Update", 40_000),
+    ("Update with 2 feeds and history", 50_000),
+    ("Update with allowlist", 40_000),
+    ("UpdateBatch with 2 updates", 70_000),
+    ("UpdateEcdsa", 80_000),
+    ("UpdateAuto", 40_000),
+    ("SetConfig", 5_000),
+    ("SetPaused", 5_000),
+    ("SetUpdaterAllowlist", 20_000),
+    ("Close", 5_000),
+    ("GetPrice", 5_000),
+    ("GetTwap", 20_000),
+];
+
+/// Reports the compute units consumed by each instruction and checks them against
+/// `COMPUTE_UNIT_BUDGETS`. Run with `--nocapture` to compare the numbers before and after
+/// a change.
+#[tokio::test]
+async fn test_compute_units() {
+    let mut env = setup().await;
+    env.set_ecdsa_signer().await;
+    // The ECDSA update is from this time.
+    env.set_clock(1740480251).await;
+    let timestamp_us = 1_740_480_250_000_000;
+    env.initialize_feed(2).await;
+    env.initialize_feed(3).await;
+    env.initialize_history(3).await;
+    env.initialize_feed(5).await;
+    let payer = env.context.payer.pubkey();
+    let program_id = pyth_lazer_solana_example::ID;
+    let update_args = UpdateArgs::new(UnmatchedFeedMode::Reject);
+    // Only price feed 5 has an updater allowlist, the other cases are unaffected.
+    env.send(&[instructions::set_updater_allowlist(
+        &program_id,
+        &payer,
+        5,
+        &[payer],
+        true,
+    )])
+    .await
+    .unwrap();
+
+    // Fill the history so that the TWAP has something to average.
+    for i in 1..=HISTORY_CAPACITY as u64 {
+        if i % 32 == 0 {
+            env.context.last_blockhash = env.context.get_new_latest_blockhash().await.unwrap();
+        }
+        let message = sign_update(
+            &env.test_signer,
+            &price_payload(timestamp_us - 5_000_000 + i * 1_000, &[(3, 100 + i as i64)]),
+        );
+        let mut instructions = env.update_instructions(&message, UnmatchedFeedMode::Reject, &[3]);
+        instructions[1]
+            .accounts
+            .push(AccountMeta::new(find_history_pda(&program_id, 3).0, false));
+        env.send(&instructions).await.unwrap();
+    }
+
+    let message = sign_update(&env.test_signer, &price_payload(timestamp_us, &[(2, 100)]));
+    let message_both = sign_update(
+        &env.test_signer,
+        &price_payload(timestamp_us, &[(2, 100), (3, 200)]),
+    );
+    let message_other = sign_update(&env.test_signer, &price_payload(timestamp_us, &[(3, 200)]));
+    let message_ecdsa = hex::decode(ECDSA_UPDATE_HEX).unwrap();
+    let message_allowlisted =
+        sign_update(&env.test_signer, &price_payload(timestamp_us, &[(5, 100)]));
+    let mut update_with_allowlist =
+        env.update_instructions(&message_allowlisted, UnmatchedFeedMode::Reject, &[5]);
+    update_with_allowlist[1]
+        .accounts
+        .push(instructions::updater_allowlist_account(&program_id, 5));
+    let mut update_with_history =
+        env.update_instructions(&message_both, UnmatchedFeedMode::Reject, &[2, 3]);
+    update_with_history[1]
+        .accounts
+        .push(AccountMeta::new(find_history_pda(&program_id, 3).0, false));
+    let cases = [
+        (
+            "Initialize",
+            vec![instructions::initialize(
+                &program_id,
+                &payer,
+                &InitializeArgs {
+                    price_feed_id: 4,
+                    max_age_us: MAX_AGE_US,
+                    accepted_channels: channel_bit(Channel::FixedRate(FixedRate::MIN).id()),
+                    max_deviation_bps_per_second: 0,
+                    deviation_mode: DeviationMode::Reject as u8,
+                    authority: payer,
+                },
+            )],
+        ),
+        (
+            "InitializeHistory",
+            vec![instructions::initialize_history(&program_id, &payer, 2)],
+        ),
+        (
+            "Update",
+            env.update_instructions(&message, UnmatchedFeedMode::Reject, &[2]),
+        ),
+        ("Update with 2 feeds and history", update_with_history),
+        ("Update with allowlist", update_with_allowlist),
+        (
+            "UpdateBatch with 2 updates",
+            instructions::update_batch(
+                &program_id,
+                &payer,
+                &env.treasury,
+                &[&message, &message_other],
+                &update_args,
+                &[2, 3],
+            ),
+        ),
+        (
+            "UpdateEcdsa",
+            instructions::update_ecdsa(
+                &program_id,
+                &payer,
+                &env.treasury,
+                &message_ecdsa,
+                &update_args,
+                &[2],
+            ),
+        ),
+        (
+            "UpdateAuto",
+            instructions::update_auto(
+                &program_id,
+                &payer,
+                &env.treasury,
+                &message,
+                &update_args,
+                &[2],
+            ),
+        ),
+        (
+            "SetConfig",
+            vec![instructions::set_config(
+                &program_id,
+                &payer,
+                &SetConfigArgs {
+                    price_feed_id: 2,
+                    max_age_us: MAX_AGE_US,
+                    accepted_channels: channel_bit(Channel::FixedRate(FixedRate::MIN).id()),
+                    max_deviation_bps_per_second: 0,
+                    deviation_mode: DeviationMode::Reject as u8,
+                },
+            )],
+        ),
+        (
+            "SetPaused",
+            vec![instructions::set_paused(&program_id, &payer, 2, true)],
+        ),
+        (
+            "SetUpdaterAllowlist",
+            vec![instructions::set_updater_allowlist(
+                &program_id,
+                &payer,
+                2,
+                &[payer],
+                true,
+            )],
+        ),
+        (
+            "Close",
+            vec![instructions::close(&program_id, &payer, 3, &payer)],
+        ),
+        (
+            "GetPrice",
+            vec![instructions::get_price(&program_id, 3, MAX_AGE_US)],
+        ),
+        (
+            "GetTwap",
+            vec![instructions::get_twap(&program_id, 3, 5_900_000)],
+        ),
+    ];
+
+    let mut over_budget = Vec::new();
+    for ((name, instructions), (budget_name, budget)) in cases.iter().zip(COMPUTE_UNIT_BUDGETS) {
+        assert_eq!(name, budget_name);
+        let units = env.compute_units(instructions).await;
+        println!("{name}: {units} CU (budget {budget})");
+        if units > *budget {
+            over_budget.push(*name);
+        }
+    }
+    assert!(over_budget.is_empty(), "over budget: {over_budget:?}");
+}